@@ -0,0 +1,57 @@
+use memoize::memoize;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+// On an `impl` block, `#[memoize]` memoizes the associated functions marked with `#[memoize]`.
+#[memoize]
+impl Point {
+    #[memoize]
+    fn count_lattice_points(radius: i64) -> usize {
+        (-radius..=radius)
+            .flat_map(|x| (-radius..=radius).map(move |y| (x, y)))
+            .filter(|(x, y)| x * x + y * y <= radius * radius)
+            .count()
+    }
+
+    #[memoize(SharedCache)]
+    fn distance_squared(&self, other: &Point) -> i64 {
+        (self.x - other.x).pow(2) + (self.y - other.y).pow(2)
+    }
+
+    fn origin() -> Point {
+        Point { x: 0, y: 0 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Version(u32, u32);
+
+impl Version {
+    // Associated functions whose signature mentions `Self` are recognized without a memoized
+    // `impl` block.
+    #[memoize]
+    fn parse(s: String) -> Option<Self> {
+        let (major, minor) = s.split_once('.')?;
+        Some(Version(major.parse().ok()?, minor.parse().ok()?))
+    }
+}
+
+fn main() {
+    assert_eq!(Point::count_lattice_points(1), 5);
+    assert_eq!(Point::count_lattice_points(1), 5);
+    assert_eq!(Point::memoized_size_count_lattice_points(), 1);
+    Point::memoized_flush_count_lattice_points();
+    assert_eq!(Point::memoized_size_count_lattice_points(), 0);
+
+    let p = Point { x: 1, y: 2 };
+    assert_eq!(p.distance_squared(&Point::origin()), 5);
+    assert_eq!(Point::memoized_size_distance_squared(), 1);
+
+    assert_eq!(Version::parse("1.2".to_string()), Some(Version(1, 2)));
+    assert_eq!(Version::parse("1.2".to_string()), Some(Version(1, 2)));
+    assert_eq!(Version::memoized_size_parse(), 1);
+}
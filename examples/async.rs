@@ -0,0 +1,59 @@
+use futures::executor::block_on;
+use memoize::memoize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+async fn fetch(url: &str) -> String {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    format!("<html>{}</html>", url)
+}
+
+// The resolved output is cached, not the future.
+#[memoize]
+async fn get(url: String) -> String {
+    fetch(&url).await
+}
+
+// The lock is never held across an `.await`, so the returned future is `Send`.
+#[memoize(SharedCache)]
+async fn get_shared(url: String) -> String {
+    fetch(&url).await
+}
+
+struct Client;
+
+impl Client {
+    #[memoize(Ignore: self)]
+    async fn get(&self, url: String) -> usize {
+        fetch(&url).await.len()
+    }
+}
+
+fn assert_send<T: Send>(t: T) -> T {
+    t
+}
+
+fn main() {
+    block_on(async {
+        assert_eq!(get("a".to_string()).await, "<html>a</html>");
+        assert_eq!(get("a".to_string()).await, "<html>a</html>");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            assert_send(get_shared("b".to_string())).await,
+            "<html>b</html>"
+        );
+        assert_eq!(get_shared("b".to_string()).await, "<html>b</html>");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+        assert_eq!(memoized_size_get_shared(), 1);
+        memoized_flush_get_shared();
+        assert_eq!(get_shared("b".to_string()).await, "<html>b</html>");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+
+        let client = Client;
+        assert_eq!(client.get("c".to_string()).await, 14);
+        assert_eq!(client.get("c".to_string()).await, 14);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 4);
+    });
+}
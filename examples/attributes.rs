@@ -0,0 +1,121 @@
+use memoize::memoize;
+
+/// Documentation, `#[must_use]` and `#[inline]` apply to the memoized function.
+#[memoize]
+#[must_use]
+#[inline]
+fn square(n: u64) -> u64 {
+    n * n
+}
+
+// Items of functions excluded by `#[cfg]` are excluded entirely.
+#[cfg(any())]
+#[memoize]
+fn excluded(n: u64) -> u64 {
+    n
+}
+
+#[cfg(feature = "full")]
+#[memoize]
+fn included(n: u64) -> u64 {
+    n
+}
+
+// Also when `#[cfg]` follows `#[memoize]`, on functions and on methods in memoized `impl` blocks.
+#[memoize]
+#[cfg(any())]
+fn excluded_below(n: u64) -> u64 {
+    n
+}
+
+#[derive(Clone)]
+struct Gated;
+
+#[memoize]
+impl Gated {
+    #[memoize]
+    #[cfg(any())]
+    fn double(n: u64) -> u64 {
+        n
+    }
+
+    #[memoize]
+    #[cfg(not(any()))]
+    fn double(n: u64) -> u64 {
+        2 * n
+    }
+}
+
+#[memoize]
+#[deprecated(note = "use `square` instead")]
+fn old_square(n: u64) -> u64 {
+    n * n
+}
+
+// Also for generic and `impl` block functions, whose caches are stored type-erased.
+#[memoize]
+#[deprecated(note = "use `Clone::clone` instead")]
+fn old_identity<T: Clone + std::hash::Hash + Eq + 'static>(t: T) -> T {
+    t
+}
+
+#[memoize]
+impl Gated {
+    #[memoize]
+    #[deprecated(note = "use `Gated` instead")]
+    fn old_new(n: u64) -> Self {
+        let _ = n;
+        Gated
+    }
+}
+
+// Lint levels apply to both the memoized and the original function.
+#[memoize]
+#[allow(clippy::too_many_arguments)]
+fn sum(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8, g: u8, h: u8) -> u32 {
+    [a, b, c, d, e, f, g, h].iter().map(|&x| x as u32).sum()
+}
+
+/// # Safety
+///
+/// `p` must be valid for reads.
+#[memoize(Ignore: p)]
+unsafe fn read(p: *const u32, offset: usize) -> u32 {
+    *p + offset as u32
+}
+
+// `#[no_mangle]` applies to the memoized function, which keeps the C ABI.
+#[memoize]
+#[no_mangle]
+extern "C" fn memoize_example_cube(n: u64) -> u64 {
+    n * n * n
+}
+
+fn main() {
+    assert_eq!(square(3), 9);
+    assert_eq!(Gated::double(2), 4);
+    assert_eq!(Gated::memoized_size_double(), 1);
+    #[cfg(feature = "full")]
+    {
+        assert_eq!(included(1), 1);
+        assert_eq!(memoized_size_included(), 1);
+    }
+    #[allow(deprecated)]
+    {
+        assert_eq!(old_square(2), 4);
+        assert_eq!(old_identity("a"), "a");
+        Gated::old_new(1);
+        assert_eq!(Gated::memoized_size_old_new(), 1);
+    }
+    assert_eq!(sum(1, 2, 3, 4, 5, 6, 7, 8), 36);
+
+    let x = 1;
+    assert_eq!(unsafe { read(&x, 2) }, 3);
+    let y = 5;
+    // The pointer is ignored, so the cached value is returned.
+    assert_eq!(unsafe { read(&y, 2) }, 3);
+
+    let cube: extern "C" fn(u64) -> u64 = memoize_example_cube;
+    assert_eq!(cube(2), 8);
+    assert_eq!(memoized_size_memoize_example_cube(), 1);
+}
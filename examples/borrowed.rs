@@ -0,0 +1,98 @@
+use memoize::memoize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// Borrowed arguments are stored in the cache as their owned form: `String` for `&str`,
+// `Vec<u8>` for `&[u8]`, `PathBuf` for `&Path`.
+#[memoize]
+fn count_words(text: &str) -> usize {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    text.split_whitespace().count()
+}
+
+#[memoize(SharedCache)]
+fn checksum(data: &[u8]) -> u32 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    data.iter().map(|&b| b as u32).sum()
+}
+
+#[memoize]
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    path.with_extension(extension)
+}
+
+// Borrowed and owned arguments can be mixed.
+#[memoize]
+fn prefix(text: &str, n: usize) -> String {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    text.chars().take(n).collect()
+}
+
+// Other `&'static T` arguments are stored as they are, so `T` needn't be `Clone`.
+#[derive(Debug, Hash, PartialEq, Eq)]
+struct Unit(&'static str);
+
+static METER: Unit = Unit("m");
+
+#[memoize]
+fn symbol(unit: &'static Unit) -> &'static str {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    unit.0
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 2)]
+fn truncate(text: &str, n: usize) -> String {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    text.chars().take(n).collect()
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 2)]
+fn parent(path: &Path) -> Option<PathBuf> {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    path.parent().map(Path::to_path_buf)
+}
+
+fn main() {
+    let text = String::from("the quick brown fox");
+    assert_eq!(count_words(&text), 4);
+    assert_eq!(count_words("the quick brown fox"), 4);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+    assert_eq!(checksum(&[1, 2, 3]), 6);
+    let data: Vec<u8> = (1..=3).collect();
+    assert_eq!(checksum(&data), 6);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+
+    let path = PathBuf::from("/tmp/file.txt");
+    assert_eq!(with_extension(&path, "md"), Path::new("/tmp/file.md"));
+    assert_eq!(
+        with_extension(Path::new("/tmp/file.txt"), "md"),
+        Path::new("/tmp/file.md")
+    );
+    assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+
+    assert_eq!(prefix(&text, 3), "the");
+    assert_eq!(prefix("the quick brown fox", 3), "the");
+    assert_eq!(prefix(&text, 5), "the q");
+    assert_eq!(CALLS.load(Ordering::SeqCst), 5);
+    assert_eq!(memoized_size_prefix(), 2);
+
+    assert_eq!(symbol(&METER), "m");
+    assert_eq!(symbol(&METER), "m");
+    assert_eq!(CALLS.load(Ordering::SeqCst), 6);
+
+    #[cfg(feature = "full")]
+    {
+        assert_eq!(truncate(&text, 3), "the");
+        assert_eq!(truncate(&text, 3), "the");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 7);
+        assert_eq!(parent(Path::new("/tmp/a")), Some(PathBuf::from("/tmp")));
+        assert_eq!(parent(Path::new("/tmp/a")), Some(PathBuf::from("/tmp")));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 8);
+    }
+}
@@ -0,0 +1,60 @@
+use memoize::memoize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Hash, PartialEq, Eq)]
+struct Name(String);
+
+impl Clone for Name {
+    fn clone(&self) -> Name {
+        CLONES.fetch_add(1, Ordering::SeqCst);
+        Name(self.0.clone())
+    }
+}
+
+#[memoize]
+fn greet(name: Name, excited: bool) -> String {
+    format!("Hello, {}{}", name.0, if excited { "!" } else { "." })
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 10)]
+fn shout(name: Name) -> String {
+    name.0.to_uppercase()
+}
+
+// Also for borrowed arguments, and keys of several arguments.
+#[cfg(feature = "full")]
+#[memoize(Capacity: 10)]
+fn whisper(name: &Name, times: usize) -> String {
+    name.0.to_lowercase().repeat(times)
+}
+
+fn clones() -> usize {
+    CLONES.swap(0, Ordering::SeqCst)
+}
+
+fn main() {
+    // On a miss, the argument is cloned to call the original function, and moved into the key.
+    greet(Name("Alice".to_string()), true);
+    assert_eq!(clones(), 1);
+    // Hits don't clone the arguments.
+    assert_eq!(greet(Name("Alice".to_string()), true), "Hello, Alice!");
+    assert_eq!(clones(), 0);
+
+    #[cfg(feature = "full")]
+    {
+        shout(Name("Bob".to_string()));
+        assert_eq!(clones(), 1);
+        assert_eq!(shout(Name("Bob".to_string())), "BOB");
+        assert_eq!(clones(), 0);
+
+        // A borrowed argument is converted into its owned form only when it is inserted.
+        let carol = Name("Carol".to_string());
+        whisper(&carol, 2);
+        assert_eq!(clones(), 1);
+        assert_eq!(whisper(&carol, 2), "carolcarol");
+        assert_eq!(clones(), 0);
+    }
+}
@@ -0,0 +1,69 @@
+use futures::executor::block_on;
+use futures::future::{join_all, FutureExt};
+use memoize::memoize;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+/// Returns `Pending` once, giving other tasks a chance to run.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// Concurrent callers with the same key await the same computation.
+#[memoize(Coalesce)]
+async fn slow_square(n: u64) -> u64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    YieldNow(false).await;
+    n * n
+}
+
+#[memoize(Coalesce)]
+async fn fallible(n: u64, fail: bool) -> u64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    YieldNow(false).await;
+    if fail {
+        panic!("computation failed");
+    }
+    n
+}
+
+fn main() {
+    block_on(async {
+        let results = join_all((0..5).map(|_| slow_square(3))).await;
+        assert_eq!(results, vec![9; 5]);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(memoized_size_slow_square(), 1);
+
+        // If the leading caller is cancelled, a waiting caller takes over.
+        let mut leader = Box::pin(slow_square(4));
+        assert!(futures::poll!(leader.as_mut()).is_pending());
+        let mut follower = Box::pin(slow_square(4));
+        assert!(futures::poll!(follower.as_mut()).is_pending());
+        drop(leader);
+        assert_eq!(follower.await, 16);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+
+        // A panicking computation (the panic message is printed) doesn't leave a stale entry
+        // behind.
+        let result = AssertUnwindSafe(fallible(1, true)).catch_unwind().await;
+        assert!(result.is_err());
+        assert_eq!(memoized_size_fallible(), 0);
+        assert_eq!(fallible(1, false).await, 1);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 5);
+    });
+}
@@ -0,0 +1,78 @@
+use memoize::memoize;
+use std::time::SystemTime;
+
+// Neither `Hash` nor `Clone`: only the `id` identifies the result.
+struct User {
+    id: u64,
+    name: String,
+    last_seen: SystemTime,
+}
+
+impl User {
+    #[memoize(Key: self.id => u64)]
+    fn shout(&self) -> String {
+        self.name.to_uppercase()
+    }
+}
+
+#[memoize(Key: user.id => u64)]
+fn greeting(user: &User) -> String {
+    format!("Hello, {}!", user.name)
+}
+
+#[memoize(SharedCache, Key: (user.id, lang.to_lowercase()) => (u64, String))]
+fn localized_greeting(user: &User, lang: &str) -> String {
+    match lang.to_lowercase().as_str() {
+        "de" => format!("Hallo, {}!", user.name),
+        _ => format!("Hello, {}!", user.name),
+    }
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 1, Key: id => u64)]
+fn lookup(id: u64, user: User) -> String {
+    format!("{}: {}", id, user.name)
+}
+
+fn main() {
+    let mut user = User {
+        id: 1,
+        name: "Alice".to_string(),
+        last_seen: SystemTime::now(),
+    };
+    assert_eq!(greeting(&user), "Hello, Alice!");
+    // Changes of other fields don't cause misses.
+    user.last_seen = SystemTime::now();
+    user.name = "Bob".to_string();
+    assert_eq!(greeting(&user), "Hello, Alice!");
+    // The helper functions take the key.
+    assert_eq!(
+        memoized_invalidate_greeting(1).as_deref(),
+        Some("Hello, Alice!")
+    );
+    assert_eq!(greeting(&user), "Hello, Bob!");
+
+    assert_eq!(user.shout(), "BOB");
+    user.name = "Carol".to_string();
+    assert_eq!(user.shout(), "BOB");
+    assert_eq!(User::memoized_size_shout(), 1);
+
+    assert_eq!(localized_greeting(&user, "DE"), "Hallo, Carol!");
+    assert_eq!(
+        memoized_peek_localized_greeting((1, "de".to_string())).as_deref(),
+        Some("Hallo, Carol!")
+    );
+
+    #[cfg(feature = "full")]
+    {
+        let user = |name: &str| User {
+            id: 0,
+            name: name.to_string(),
+            last_seen: SystemTime::UNIX_EPOCH,
+        };
+        assert_eq!(lookup(1, user("Alice")), "1: Alice");
+        assert_eq!(lookup(1, user("Bob")), "1: Alice");
+        assert_eq!(lookup(2, user("Bob")), "2: Bob");
+        assert_eq!(memoized_stats_lookup().evictions, 1);
+    }
+}
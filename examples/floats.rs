@@ -0,0 +1,54 @@
+use memoize::{memoize, FloatKey};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// `f32` and `f64` arguments are stored by their bit pattern.
+#[memoize]
+fn integrate(a: f64, b: f64, steps: u32) -> f64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    let h = (b - a) / steps as f64;
+    (0..steps)
+        .map(|i| (a + (i as f64 + 0.5) * h).powi(2) * h)
+        .sum()
+}
+
+#[memoize(FloatKeys: Bitwise)]
+fn sign(x: &f32) -> f32 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    x.signum()
+}
+
+fn cents(x: &f64) -> FloatKey {
+    FloatKey::canonical(&(x * 100.0).round())
+}
+
+// `KeyWith` functions of floating-point arguments return a `FloatKey`.
+#[memoize(KeyWith: price => cents)]
+fn tax(price: f64) -> f64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    price * 0.2
+}
+
+fn main() {
+    let area = integrate(0.0, 3.0, 1000);
+    assert!((area - 9.0).abs() < 1e-3);
+    assert_eq!(integrate(0.0, 3.0, 1000), area);
+    // By default, `-0.0` is the same key as `0.0`, and all NaNs are the same key.
+    assert_eq!(integrate(-0.0, 3.0, 1000), area);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    assert!(integrate(f64::NAN, 1.0, 10).is_nan());
+    assert!(integrate(-f64::NAN, 1.0, 10).is_nan());
+    assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    assert_eq!(memoized_peek_integrate(0.0, 3.0, 1000), Some(area));
+
+    // With `FloatKeys: Bitwise`, `-0.0` and `0.0` are different keys.
+    assert_eq!(sign(&0.0), 1.0);
+    assert_eq!(sign(&-0.0), -1.0);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 4);
+
+    // Prices are cached by the cent.
+    assert_eq!(tax(10.0), 2.0);
+    assert_eq!(tax(10.001), 2.0);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 5);
+}
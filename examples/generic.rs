@@ -0,0 +1,47 @@
+use memoize::memoize;
+use std::fmt::Debug;
+use std::str::FromStr;
+
+// Every instantiation (here: `parse::<u32>` and `parse::<f64>`) has its own cache.
+#[memoize]
+fn parse<T: FromStr + Clone + 'static>(s: String) -> T
+where
+    T::Err: Debug,
+{
+    println!("parsing {}", s);
+    s.parse().unwrap()
+}
+
+#[memoize(SharedCache)]
+fn repeat<T: Clone + Debug + Eq + std::hash::Hash + Send + 'static, const N: usize>(
+    t: T,
+) -> Vec<T> {
+    vec![t; N]
+}
+
+struct Formatter;
+
+impl Formatter {
+    #[memoize(Ignore: self)]
+    fn format<T: Debug + Clone + Eq + std::hash::Hash + 'static>(&self, t: T) -> String {
+        format!("{:?}", t)
+    }
+}
+
+fn main() {
+    assert_eq!(parse::<u32>("42".to_string()), 42);
+    assert_eq!(parse::<u32>("42".to_string()), 42);
+    assert_eq!(parse::<f64>("42".to_string()), 42.0);
+    assert_eq!(memoized_size_parse(), 2);
+    // Flushing clears the caches of all instantiations.
+    memoized_flush_parse();
+    assert_eq!(memoized_size_parse(), 0);
+
+    assert_eq!(repeat::<_, 2>('a'), vec!['a', 'a']);
+    assert_eq!(repeat::<_, 3>('a'), vec!['a', 'a', 'a']);
+    assert_eq!(memoized_size_repeat(), 2);
+
+    assert_eq!(Formatter.format(1u8), "1");
+    assert_eq!(Formatter.format("a"), "\"a\"");
+    assert_eq!(Formatter::memoized_size_format(), 2);
+}
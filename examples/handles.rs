@@ -0,0 +1,55 @@
+use memoize::{memoize, CacheControl};
+
+#[memoize]
+fn square(n: u64) -> u64 {
+    n * n
+}
+
+#[cfg(feature = "full")]
+#[memoize(SharedCache, Capacity: 10)]
+fn cube(n: u64) -> u64 {
+    n * n * n
+}
+
+struct Math;
+
+#[memoize]
+impl Math {
+    // Inside an `impl` block, the handle is an associated constant.
+    #[memoize]
+    fn double(n: u64) -> u64 {
+        n * 2
+    }
+}
+
+/// Maintenance code working on any memoized function.
+fn flush_if_larger(cache: &dyn CacheControl, max: usize) -> bool {
+    if cache.len() > max {
+        cache.flush();
+        return true;
+    }
+    false
+}
+
+fn main() {
+    square(1);
+    square(2);
+    assert_eq!(SQUARE_CACHE.name(), "square");
+    assert_eq!(SQUARE_CACHE.len(), 2);
+    assert_eq!(SQUARE_CACHE.capacity(), None);
+    assert!(flush_if_larger(&SQUARE_CACHE, 1));
+    assert!(SQUARE_CACHE.is_empty());
+
+    #[cfg(feature = "full")]
+    {
+        cube(2);
+        assert_eq!(CUBE_CACHE.capacity(), Some(10));
+        assert!(!flush_if_larger(&CUBE_CACHE, 1));
+    }
+
+    Math::double(2);
+    let caches: Vec<&dyn CacheControl> = vec![&SQUARE_CACHE, &Math::DOUBLE_CACHE];
+    let total: usize = caches.iter().map(|c| c.len()).sum();
+    assert_eq!(total, 1);
+    println!("{:?}", Math::DOUBLE_CACHE);
+}
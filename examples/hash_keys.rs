@@ -0,0 +1,52 @@
+use memoize::memoize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// Only a digest of the document is stored, not a copy of it.
+#[memoize(HashKeys)]
+fn word_count(document: Vec<u8>) -> usize {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    document.split(|&b| b == b' ').count()
+}
+
+// With `HashKeys`, memoized arguments only need to be `Hash`-able.
+#[derive(Hash)]
+struct Matrix {
+    rows: Vec<Vec<i64>>,
+}
+
+#[memoize(SharedCache, HashKeys: Verify)]
+fn trace(matrix: Matrix, scale: i64) -> i64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    (0..matrix.rows.len())
+        .map(|i| matrix.rows[i][i] * scale)
+        .sum()
+}
+
+#[memoize(HashKeys)]
+fn first_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().to_string()
+}
+
+fn main() {
+    let document = b"a large document".repeat(1000);
+    assert_eq!(word_count(document.clone()), 2001);
+    assert_eq!(word_count(document), 2001);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+    let identity = || Matrix {
+        rows: vec![vec![1, 0], vec![0, 1]],
+    };
+    assert_eq!(trace(identity(), 3), 6);
+    assert_eq!(trace(identity(), 3), 6);
+    assert_eq!(trace(identity(), 2), 4);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+    assert_eq!(memoized_invalidate_trace(identity(), 2), Some(4));
+
+    assert_eq!(first_line("title\nbody"), "title");
+    assert_eq!(
+        memoized_peek_first_line("title\nbody").as_deref(),
+        Some("title")
+    );
+}
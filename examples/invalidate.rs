@@ -0,0 +1,89 @@
+use memoize::{memoize, InstanceCache};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[memoize]
+fn lookup(user: &str, field: u32) -> String {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    format!("{}/{}", user, field)
+}
+
+#[cfg(feature = "full")]
+#[memoize(SharedCache, Capacity: 10, TimeToLive: std::time::Duration::from_secs(60))]
+fn square(n: u64) -> u64 {
+    n * n
+}
+
+#[memoize(NoOriginal)]
+fn parse<T: std::str::FromStr + Clone + 'static>(s: String) -> Option<T> {
+    s.parse().ok()
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Account(u32);
+
+impl Account {
+    // The receiver is part of the key, so the helper takes it, too.
+    #[memoize]
+    fn balance(&self, day: u32) -> u64 {
+        (self.0 * day) as u64
+    }
+}
+
+struct Session {
+    memoize_cache: InstanceCache,
+}
+
+impl Session {
+    #[memoize(PerInstance)]
+    fn permissions(&self, resource: String) -> Vec<String> {
+        vec![resource]
+    }
+}
+
+fn main() {
+    assert_eq!(lookup("alice", 1), "alice/1");
+    assert_eq!(lookup("bob", 1), "bob/1");
+    // Only the entry for "alice" is removed.
+    assert_eq!(
+        memoized_invalidate_lookup("alice", 1),
+        Some("alice/1".to_string())
+    );
+    assert_eq!(memoized_invalidate_lookup("alice", 1), None);
+    assert_eq!(memoized_size_lookup(), 1);
+    lookup("alice", 1);
+    lookup("bob", 1);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+
+    #[cfg(feature = "full")]
+    {
+        square(3);
+        assert_eq!(memoized_invalidate_square(3), Some(9));
+        assert_eq!(memoized_size_square(), 0);
+    }
+
+    parse::<u32>("1".to_string());
+    parse::<i64>("1".to_string());
+    // Generic functions need the instantiation to invalidate.
+    assert_eq!(
+        memoized_invalidate_parse::<u32>("1".to_string()),
+        Some(Some(1))
+    );
+    assert_eq!(memoized_size_parse(), 1);
+
+    let account = Account(2);
+    account.balance(3);
+    Account(4).balance(3);
+    assert_eq!(account.memoized_invalidate_balance(3), Some(6));
+    assert_eq!(Account::memoized_size_balance(), 1);
+
+    let session = Session {
+        memoize_cache: InstanceCache::new(),
+    };
+    session.permissions("files".to_string());
+    assert!(session
+        .memoized_invalidate_permissions("files".to_string())
+        .is_some());
+    assert_eq!(session.memoized_size_permissions(), 0);
+}
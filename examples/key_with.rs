@@ -0,0 +1,44 @@
+use memoize::memoize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+static NORMALIZED: AtomicUsize = AtomicUsize::new(0);
+
+fn lowercase(s: &str) -> String {
+    NORMALIZED.fetch_add(1, Ordering::SeqCst);
+    s.to_lowercase()
+}
+
+fn trimmed(s: &str) -> String {
+    s.trim().to_string()
+}
+
+// Host names are case-insensitive, so `Example.COM` and `example.com` share an entry.
+#[memoize(KeyWith: host => lowercase)]
+fn resolve(host: &str, port: u16) -> String {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    // The original function still gets the argument as passed.
+    format!("{}:{}", host, port)
+}
+
+#[memoize(SharedCache, KeyWith: query => trimmed, KeyWith: table => lowercase)]
+fn search(table: String, query: String) -> usize {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    table.len() + query.len()
+}
+
+fn main() {
+    assert_eq!(resolve("Example.COM", 80), "Example.COM:80");
+    assert_eq!(resolve("example.com", 80), "Example.COM:80");
+    assert_eq!(resolve("example.com", 443), "example.com:443");
+    assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    // Each argument is normalized once per call, also when its value is computed and inserted.
+    assert_eq!(NORMALIZED.load(Ordering::SeqCst), 3);
+    // The helper functions normalize their arguments, too.
+    assert!(memoized_peek_resolve("EXAMPLE.com", 443).is_some());
+
+    assert_eq!(search("Users".to_string(), " alice ".to_string()), 12);
+    assert_eq!(search("users".to_string(), "alice".to_string()), 12);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+    assert_eq!(memoized_size_search(), 1);
+}
@@ -0,0 +1,45 @@
+use memoize::memoize;
+use std::collections::HashMap;
+
+// Interns strings: equal strings are leaked once, and share the same address.
+#[memoize(Leak)]
+fn intern(s: &str) -> String {
+    s.to_string()
+}
+
+// Doesn't implement `Clone`.
+#[derive(Debug)]
+struct Config {
+    entries: HashMap<String, String>,
+}
+
+#[memoize(Leak)]
+fn parse_config(text: &str) -> Config {
+    let entries = text
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+    Config { entries }
+}
+
+fn main() {
+    let a: &'static String = intern("symbol");
+    let b = intern(&String::from("symbol"));
+    assert!(std::ptr::eq(a, b));
+    assert!(!std::ptr::eq(a, intern("other")));
+
+    // Interned values are shared between threads.
+    let c = std::thread::spawn(|| intern("symbol")).join().unwrap();
+    assert!(std::ptr::eq(a, c));
+
+    let config = parse_config("a = 1\nb = 2");
+    assert_eq!(config.entries["b"], "2");
+    assert!(std::ptr::eq(config, parse_config("a = 1\nb = 2")));
+
+    // After flushing, the old reference stays valid, but a new value is leaked.
+    memoized_flush_intern();
+    let d = intern("symbol");
+    assert_eq!(a, d);
+    assert!(!std::ptr::eq(a, d));
+}
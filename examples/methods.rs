@@ -0,0 +1,56 @@
+use memoize::memoize;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Polynomial {
+    coefficients: Vec<i64>,
+}
+
+impl Polynomial {
+    // `self` is part of the key, as `Polynomial` is `Clone` and `Hash`.
+    #[memoize]
+    fn eval(&self, x: i64) -> i64 {
+        println!("eval at {}", x);
+        self.coefficients.iter().rev().fold(0, |acc, c| acc * x + c)
+    }
+}
+
+/// Note that `Service` deliberately does not implement [`Clone`] or [`Hash`].
+struct Service {
+    calls: std::cell::Cell<u32>,
+}
+
+impl Service {
+    // `self` is ignored, so all `Service`s share the same results.
+    #[memoize(Ignore: self, SharedCache)]
+    pub fn lookup(&self, key: String) -> usize {
+        self.calls.set(self.calls.get() + 1);
+        key.len()
+    }
+}
+
+fn main() {
+    let p = Polynomial {
+        coefficients: vec![1, 2, 3],
+    };
+    let q = Polynomial {
+        coefficients: vec![3, 2, 1],
+    };
+    assert_eq!(p.eval(2), 17);
+    assert_eq!(p.eval(2), 17);
+    assert_eq!(q.eval(2), 11);
+    assert_eq!(Polynomial::memoized_size_eval(), 2);
+    Polynomial::memoized_flush_eval();
+    assert_eq!(Polynomial::memoized_size_eval(), 0);
+    // The original method is still available.
+    assert_eq!(p.memoized_original_eval(2), 17);
+
+    let s = Service {
+        calls: std::cell::Cell::new(0),
+    };
+    assert_eq!(s.lookup("abc".to_string()), 3);
+    assert_eq!(s.lookup("abc".to_string()), 3);
+    assert_eq!(s.calls.get(), 1);
+    assert_eq!(Service::memoized_size_lookup(), 1);
+    Service::memoized_flush_lookup();
+    assert_eq!(Service::memoized_size_lookup(), 0);
+}
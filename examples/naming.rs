@@ -0,0 +1,49 @@
+mod library {
+    use memoize::memoize;
+
+    // The original function is available (and documented) as `raw_fib`, and the helper functions
+    // are only visible within this crate.
+    #[memoize(OriginalName: raw_fib, HelperVisibility: pub(crate))]
+    pub fn fib(n: u64) -> u64 {
+        if n < 2 {
+            n
+        } else {
+            fib(n - 1) + fib(n - 2)
+        }
+    }
+
+    // No original function is generated, and the helper functions are private.
+    #[memoize(NoOriginal, HelperVisibility: pub(self))]
+    pub fn double(n: u64) -> u64 {
+        n * 2
+    }
+
+    pub fn double_cache_size() -> usize {
+        memoized_size_double()
+    }
+
+    pub struct Counter;
+
+    impl Counter {
+        // Inside an `impl` block, the original function is private.
+        #[memoize(NoOriginal, Ignore: self)]
+        pub fn triple(&self, n: u64) -> u64 {
+            n * 3
+        }
+    }
+}
+
+use library::*;
+
+fn main() {
+    assert_eq!(fib(20), 6765);
+    assert_eq!(raw_fib(20), 6765);
+    assert_eq!(memoized_size_fib(), 21);
+    memoized_flush_fib();
+
+    assert_eq!(double(2), 4);
+    assert_eq!(double_cache_size(), 1);
+
+    assert_eq!(Counter.triple(2), 6);
+    assert_eq!(Counter::memoized_size_triple(), 1);
+}
@@ -1,8 +1,8 @@
 use memoize::memoize;
 
-// Patterns in memoized function arguments must be bound by name.
+// Arguments can be destructured; the cache is keyed by the whole argument.
 #[memoize]
-fn manhattan_distance(_p1 @ (x1, y1): (i32, i32), _p2 @ (x2, y2): (i32, i32)) -> i32 {
+fn manhattan_distance((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> i32 {
     (x1 - x2).abs() + (y1 - y2).abs()
 }
 
@@ -12,14 +12,41 @@ enum OnlyOne {
 }
 
 #[memoize]
-fn get_value(_enum @ OnlyOne::Value(value): OnlyOne) -> i32 {
+fn get_value(OnlyOne::Value(value): OnlyOne) -> i32 {
     value
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[memoize]
+fn norm(Point { x, y }: Point, scale: i32) -> i32 {
+    (x.abs() + y.abs()) * scale
+}
+
+// Bindings with `@` still work, too.
+#[memoize]
+fn width(_range @ (from, to): (i32, i32)) -> i32 {
+    to - from
+}
+
 fn main() {
     // `manhattan_distance` is only called once here.
     assert_eq!(manhattan_distance((1, 1), (1, 3)), 2);
+    assert_eq!(manhattan_distance((1, 1), (1, 3)), 2);
+    assert_eq!(memoized_size_manhattan_distance(), 1);
 
     // Same with `get_value`.
     assert_eq!(get_value(OnlyOne::Value(0)), 0);
+    assert_eq!(get_value(OnlyOne::Value(0)), 0);
+    assert_eq!(memoized_size_get_value(), 1);
+
+    assert_eq!(norm(Point { x: 1, y: -2 }, 2), 6);
+    assert_eq!(norm(Point { x: 1, y: -2 }, 3), 9);
+    assert_eq!(memoized_size_norm(), 2);
+
+    assert_eq!(width((2, 5)), 3);
 }
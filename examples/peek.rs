@@ -0,0 +1,50 @@
+use memoize::memoize;
+
+#[memoize]
+fn render(page: &str) -> String {
+    format!("<html>{}</html>", page)
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 2)]
+fn square(n: u64) -> u64 {
+    n * n
+}
+
+#[cfg(feature = "full")]
+#[memoize(TimeToLive: std::time::Duration::from_secs(3600))]
+fn fetch(url: String) -> String {
+    url
+}
+
+fn main() {
+    // Serve cached pages only, without rendering on a miss.
+    assert_eq!(memoized_peek_render("index"), None);
+    render("index");
+    assert_eq!(
+        memoized_peek_render("index").as_deref(),
+        Some("<html>index</html>")
+    );
+
+    #[cfg(feature = "full")]
+    {
+        square(1);
+        square(2);
+        // Peeking doesn't update the recency of an entry, so 1 is evicted next.
+        assert_eq!(memoized_peek_square(1), Some(1));
+        square(3);
+        assert_eq!(memoized_peek_square(1), None);
+
+        // `memoized_get_*()` marks the entry as most recently used, so 3 is evicted instead.
+        assert_eq!(memoized_get_square(2), Some(4));
+        square(4);
+        assert_eq!(memoized_peek_square(3), None);
+        assert_eq!(memoized_peek_square(2), Some(4));
+
+        // Expired entries aren't returned.
+        fetch("a".to_string());
+        memoized_prime_fetch_with_ttl("b".to_string(), "b".to_string(), std::time::Duration::ZERO);
+        assert!(memoized_peek_fetch("a".to_string()).is_some());
+        assert!(memoized_peek_fetch("b".to_string()).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use memoize::{memoize, InstanceCache};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Document {
+    text: String,
+    memoize_cache: InstanceCache,
+}
+
+impl Document {
+    fn new(text: &str) -> Document {
+        Document {
+            text: text.to_string(),
+            memoize_cache: InstanceCache::new(),
+        }
+    }
+
+    // The cache is stored in `self.memoize_cache`, and dropped together with the `Document`.
+    #[memoize(PerInstance)]
+    fn count(&self, word: String) -> usize {
+        println!("counting {}", word);
+        self.text.split_whitespace().filter(|w| *w == word).count()
+    }
+
+    #[memoize(PerInstance)]
+    fn longest(&self) -> String {
+        self.text
+            .split_whitespace()
+            .max_by_key(|w| w.len())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+struct Counter {
+    step: u32,
+    cache: InstanceCache,
+}
+
+impl Counter {
+    // A different field name can be given explicitly.
+    #[memoize(PerInstance: cache)]
+    fn times(&self, n: u32) -> u32 {
+        self.step * n
+    }
+}
+
+fn main() {
+    let a = Document::new("a rose is a rose is a rose");
+    let b = Document::new("a b c");
+
+    assert_eq!(a.count("rose".to_string()), 3);
+    assert_eq!(a.count("rose".to_string()), 3);
+    assert_eq!(b.count("rose".to_string()), 0);
+    assert_eq!(a.longest(), "rose");
+
+    assert_eq!(a.memoized_size_count(), 1);
+    assert_eq!(a.memoized_size_longest(), 1);
+    assert_eq!(a.memoize_cache.len(), 2);
+    assert_eq!(b.memoized_size_count(), 1);
+
+    // Clones start out with an empty cache, and still compare equal.
+    let c = a.clone();
+    assert_eq!(c.memoize_cache.len(), 0);
+    assert_eq!(a, c);
+
+    a.memoized_flush_count();
+    assert_eq!(a.memoized_size_count(), 0);
+    assert_eq!(a.memoized_size_longest(), 1);
+    a.memoize_cache.clear();
+    assert!(a.memoize_cache.is_empty());
+
+    let three = Counter {
+        step: 3,
+        cache: InstanceCache::new(),
+    };
+    let four = Counter {
+        step: 4,
+        cache: InstanceCache::new(),
+    };
+    assert_eq!(three.times(2), 6);
+    assert_eq!(four.times(2), 8);
+}
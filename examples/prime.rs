@@ -0,0 +1,55 @@
+use memoize::memoize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[memoize]
+fn exchange_rate(from: &str, to: &str) -> f64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    if from == to {
+        1.0
+    } else {
+        unimplemented!("querying exchange rates")
+    }
+}
+
+// An argument named `value` doesn't clash with the value parameter of the helper.
+#[memoize(SharedCache)]
+fn double(value: u64) -> u64 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    value * 2
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 100, TimeToLive: std::time::Duration::from_secs(3600))]
+fn fetch(url: String) -> String {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    format!("<html>{}</html>", url)
+}
+
+fn main() {
+    // Results computed elsewhere can be inserted without calling the original function.
+    memoized_prime_exchange_rate("EUR", "USD", 1.08);
+    assert_eq!(exchange_rate("EUR", "USD"), 1.08);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+
+    memoized_prime_double(2, 5);
+    assert_eq!(double(2), 5);
+    assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+
+    #[cfg(feature = "full")]
+    {
+        use std::time::Duration;
+
+        memoized_prime_fetch("a".to_string(), "<html>primed</html>".to_string());
+        assert_eq!(fetch("a".to_string()), "<html>primed</html>");
+        // An entry can have its own time to live, overriding `TimeToLive`.
+        memoized_prime_fetch_with_ttl(
+            "b".to_string(),
+            "<html>stale</html>".to_string(),
+            Duration::ZERO,
+        );
+        assert_eq!(fetch("b".to_string()), "<html>b</html>");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
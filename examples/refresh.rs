@@ -0,0 +1,31 @@
+use memoize::memoize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static PRICE: AtomicU64 = AtomicU64::new(100);
+
+#[memoize(SharedCache)]
+fn price(item: &str) -> u64 {
+    PRICE.load(Ordering::SeqCst) + item.len() as u64
+}
+
+#[memoize(Ignore: calls)]
+fn square(n: u64, calls: &mut u32) -> u64 {
+    *calls += 1;
+    n * n
+}
+
+fn main() {
+    assert_eq!(price("apple"), 105);
+    PRICE.store(200, Ordering::SeqCst);
+    // The cached price is stale until it is refreshed.
+    assert_eq!(price("apple"), 105);
+    assert_eq!(memoized_refresh_price("apple"), 205);
+    assert_eq!(price("apple"), 205);
+
+    // `Ignore`d arguments are passed to the original function as well.
+    let mut calls = 0;
+    square(3, &mut calls);
+    assert_eq!(memoized_refresh_square(3, &mut calls), 9);
+    square(3, &mut calls);
+    assert_eq!(calls, 2);
+}
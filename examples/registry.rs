@@ -0,0 +1,75 @@
+use memoize::{memoize, CacheControl, Storage};
+
+#[memoize]
+fn square(n: u64) -> u64 {
+    n * n
+}
+
+#[memoize(SharedCache)]
+fn parse<T: std::str::FromStr + Clone + Send + 'static>(s: String) -> Option<T> {
+    s.parse().ok()
+}
+
+#[memoize]
+fn cube(n: u64) -> u64 {
+    n * n * n
+}
+
+#[cfg(feature = "full")]
+struct Cached<T>(T);
+
+#[cfg(feature = "full")]
+impl<T: Clone + 'static> Cached<T> {
+    #[memoize(Ignore: self, TimeToLive: std::time::Duration::from_secs(60))]
+    fn get(&self, n: u64) -> u64 {
+        n + 1
+    }
+}
+
+fn main() {
+    // Functions are registered on their first call.
+    assert!(memoize::caches().is_empty());
+
+    square(2);
+    parse::<u8>("1".to_string());
+    parse::<f32>("1".to_string());
+    let mut names: Vec<_> = memoize::caches().iter().map(|c| c.name()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["parse", "square"]);
+
+    // Also when they are primed.
+    memoized_prime_cube(3, 27);
+    assert!(memoize::caches().iter().any(|c| c.name() == "cube"));
+
+    #[cfg(feature = "full")]
+    {
+        Cached(()).get(1);
+        let get = memoize::caches()
+            .into_iter()
+            .find(|c| c.name() == "get")
+            .unwrap();
+        assert_eq!(get.time_to_live(), Some(std::time::Duration::from_secs(60)));
+    }
+
+    // Report the caches, e.g. from an admin endpoint.
+    for cache in memoize::caches() {
+        println!(
+            "{}::{}: {} entries ({:?})",
+            cache.module_path(),
+            cache.name(),
+            cache.len(),
+            cache.storage()
+        );
+    }
+    let parse_cache = memoize::caches()
+        .into_iter()
+        .find(|c| c.name() == "parse")
+        .unwrap();
+    assert_eq!(parse_cache.storage(), Storage::Shared);
+    assert_eq!(parse_cache.len(), 2);
+
+    memoize::flush_all();
+    assert!(memoize::caches().iter().all(|c| c.is_empty()));
+    assert_eq!(memoized_size_cube(), 0);
+    assert_eq!(cube(2), 8);
+}
@@ -0,0 +1,50 @@
+use futures::executor::block_on;
+use memoize::memoize;
+use std::rc::Rc;
+use std::sync::Arc;
+
+// Doesn't implement `Clone`, so it can only be memoized with `ReturnShared`.
+#[derive(Debug, PartialEq)]
+struct Graph {
+    edges: Vec<(usize, usize)>,
+}
+
+// Thread-local caches store and return an `Rc`.
+#[memoize(ReturnShared)]
+fn complete_graph(n: usize) -> Graph {
+    let edges = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .collect();
+    Graph { edges }
+}
+
+// Shared caches store and return an `Arc`.
+#[memoize(SharedCache, ReturnShared)]
+fn squares(n: u64) -> Vec<u64> {
+    (0..n).map(|i| i * i).collect()
+}
+
+#[memoize(Coalesce, ReturnShared)]
+async fn cubes(n: u64) -> Vec<u64> {
+    (0..n).map(|i| i * i * i).collect()
+}
+
+fn main() {
+    let g1: Rc<Graph> = complete_graph(4);
+    let g2 = complete_graph(4);
+    assert_eq!(g1.edges.len(), 6);
+    // Both calls return the same cached value.
+    assert!(Rc::ptr_eq(&g1, &g2));
+    assert_eq!(*g1, memoized_original_complete_graph(4));
+
+    let s1: Arc<Vec<u64>> = squares(1000);
+    let s2 = std::thread::spawn(|| squares(1000)).join().unwrap();
+    assert!(Arc::ptr_eq(&s1, &s2));
+
+    block_on(async {
+        let c1 = cubes(3).await;
+        let c2 = cubes(3).await;
+        assert_eq!(*c1, vec![0, 1, 8]);
+        assert!(Arc::ptr_eq(&c1, &c2));
+    });
+}
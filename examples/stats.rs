@@ -0,0 +1,62 @@
+use memoize::{memoize, CacheControl, CacheStats};
+
+#[memoize]
+fn double(n: u64) -> u64 {
+    n * 2
+}
+
+#[memoize(SharedCache)]
+fn triple(n: u64) -> u64 {
+    n * 3
+}
+
+#[cfg(feature = "full")]
+#[memoize(Capacity: 2)]
+fn square(n: u64) -> u64 {
+    n * n
+}
+
+#[cfg(feature = "full")]
+#[memoize(TimeToLive: std::time::Duration::from_secs(3600))]
+fn fetch(url: String) -> String {
+    url
+}
+
+fn main() {
+    double(1);
+    double(1);
+    double(2);
+    // Statistics are shared by all threads, even for thread-local caches.
+    std::thread::spawn(|| double(1)).join().unwrap();
+    let stats = memoized_stats_double();
+    assert_eq!((stats.hits, stats.misses, stats.inserts), (1, 3, 3));
+    assert_eq!(stats.hit_ratio(), Some(0.25));
+
+    memoized_prime_triple(1, 3);
+    triple(1);
+    TRIPLE_CACHE.flush();
+    assert_eq!(
+        TRIPLE_CACHE.stats(),
+        CacheStats {
+            hits: 1,
+            inserts: 1,
+            flushes: 1,
+            ..CacheStats::default()
+        }
+    );
+
+    #[cfg(feature = "full")]
+    {
+        square(1);
+        square(2);
+        square(2);
+        square(3);
+        assert_eq!(memoized_stats_square().evictions, 1);
+
+        fetch("a".to_string());
+        memoized_prime_fetch_with_ttl("b".to_string(), "b".to_string(), std::time::Duration::ZERO);
+        fetch("b".to_string());
+        let stats = memoized_stats_fetch();
+        assert_eq!((stats.misses, stats.expirations), (2, 1));
+    }
+}
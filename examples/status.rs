@@ -0,0 +1,49 @@
+use memoize::{memoize, CacheStatus};
+
+#[memoize]
+fn render(page: &str) -> String {
+    format!("<html>{}</html>", page)
+}
+
+#[memoize(SharedCache, ReturnShared)]
+fn load(id: u32) -> Vec<u32> {
+    vec![id]
+}
+
+#[cfg(feature = "full")]
+#[memoize(TimeToLive: std::time::Duration::from_secs(3600))]
+fn fetch(url: String) -> String {
+    url
+}
+
+fn main() {
+    assert_eq!(
+        render_with_status("index"),
+        ("<html>index</html>".to_string(), CacheStatus::Miss)
+    );
+    assert_eq!(
+        render_with_status("index").1,
+        CacheStatus::Hit { age: None }
+    );
+    assert_eq!(render("index"), "<html>index</html>");
+    assert_eq!(memoized_stats_render().hits, 2);
+
+    let (value, status) = load_with_status(1);
+    assert!(!status.is_hit());
+    assert!(std::sync::Arc::ptr_eq(&value, &load(1)));
+
+    #[cfg(feature = "full")]
+    {
+        fetch("a".to_string());
+        match fetch_with_status("a".to_string()) {
+            (_, CacheStatus::Hit { age: Some(age) }) => {
+                assert!(age < std::time::Duration::from_secs(3600))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        memoized_prime_fetch_with_ttl("b".to_string(), "b".to_string(), std::time::Duration::ZERO);
+        assert_eq!(fetch_with_status("b".to_string()).1, CacheStatus::Expired);
+        assert!(fetch_with_status("b".to_string()).1.is_hit());
+    }
+}
@@ -1,10 +1,15 @@
 #![crate_type = "proc-macro"]
 #![allow(unused_imports)] // Spurious complaints about a required trait import.
-use syn::{self, parse, parse_macro_input, spanned::Spanned, Expr, ExprCall, ItemFn, Path};
+use syn::{
+    self, ext::IdentExt, parse, parse_macro_input, spanned::Spanned, Expr, ExprCall, ItemFn, Path,
+};
 
 use proc_macro::TokenStream;
 use quote::{self, ToTokens};
 
+/// Number of memoized arguments up to which `memoize::keys` has a `KeyView` trait.
+const MAX_KEY_VIEW_ARITY: usize = 8;
+
 mod kw {
     syn::custom_keyword!(Capacity);
     syn::custom_keyword!(TimeToLive);
@@ -12,6 +17,20 @@ mod kw {
     syn::custom_keyword!(CustomHasher);
     syn::custom_keyword!(HasherInit);
     syn::custom_keyword!(Ignore);
+    syn::custom_keyword!(PerInstance);
+    syn::custom_keyword!(Coalesce);
+    syn::custom_keyword!(ReturnShared);
+    syn::custom_keyword!(Leak);
+    syn::custom_keyword!(OriginalName);
+    syn::custom_keyword!(HelperVisibility);
+    syn::custom_keyword!(NoOriginal);
+    syn::custom_keyword!(Key);
+    syn::custom_keyword!(KeyWith);
+    syn::custom_keyword!(HashKeys);
+    syn::custom_keyword!(Verify);
+    syn::custom_keyword!(FloatKeys);
+    syn::custom_keyword!(Canonical);
+    syn::custom_keyword!(Bitwise);
     syn::custom_punctuation!(Colon, :);
 }
 
@@ -23,6 +42,18 @@ struct CacheOptions {
     custom_hasher: Option<Path>,
     custom_hasher_initializer: Option<ExprCall>,
     ignore: Vec<syn::Ident>,
+    per_instance: Option<syn::Ident>,
+    coalesce: bool,
+    return_shared: bool,
+    leak: bool,
+    original_name: Option<syn::Ident>,
+    helper_visibility: Option<syn::Visibility>,
+    no_original: bool,
+    key: Option<(Expr, Box<syn::Type>)>,
+    key_with: Vec<(syn::Ident, Path)>,
+    hash_keys: bool,
+    verify_keys: bool,
+    bitwise_floats: bool,
 }
 
 #[derive(Clone)]
@@ -33,6 +64,17 @@ enum CacheOption {
     CustomHasher(Path),
     HasherInit(ExprCall),
     Ignore(syn::Ident),
+    PerInstance(syn::Ident),
+    Coalesce,
+    ReturnShared,
+    Leak,
+    OriginalName(syn::Ident),
+    HelperVisibility(syn::Visibility),
+    NoOriginal,
+    Key(Expr, Box<syn::Type>),
+    KeyWith(syn::Ident, Path),
+    HashKeys { verify: bool },
+    FloatKeys { bitwise: bool },
 }
 
 // To extend option parsing, add functionality here.
@@ -49,8 +91,15 @@ impl parse::Parse for CacheOption {
             input.parse::<kw::Capacity>().unwrap();
             input.parse::<kw::Colon>().unwrap();
             let cap: syn::LitInt = input.parse().unwrap();
+            let max_entries = cap.base10_parse()?;
+            if max_entries == 0 {
+                return Err(syn::Error::new(
+                    cap.span(),
+                    "Capacity must be greater than zero!",
+                ));
+            }
 
-            return Ok(CacheOption::LRUMaxEntries(cap.base10_parse()?));
+            return Ok(CacheOption::LRUMaxEntries(max_entries));
         }
         if la.peek(kw::TimeToLive) {
             #[cfg(not(feature = "full"))]
@@ -83,9 +132,86 @@ impl parse::Parse for CacheOption {
         if la.peek(kw::Ignore) {
             input.parse::<kw::Ignore>().unwrap();
             input.parse::<kw::Colon>().unwrap();
-            let ignore_ident = input.parse::<syn::Ident>().unwrap();
+            // `self` is a keyword, so it needs to be parsed specially.
+            let ignore_ident = input.call(syn::Ident::parse_any)?;
             return Ok(CacheOption::Ignore(ignore_ident));
         }
+        if la.peek(kw::Coalesce) {
+            input.parse::<kw::Coalesce>().unwrap();
+            return Ok(CacheOption::Coalesce);
+        }
+        if la.peek(kw::ReturnShared) {
+            input.parse::<kw::ReturnShared>().unwrap();
+            return Ok(CacheOption::ReturnShared);
+        }
+        if la.peek(kw::Leak) {
+            input.parse::<kw::Leak>().unwrap();
+            return Ok(CacheOption::Leak);
+        }
+        if la.peek(kw::OriginalName) {
+            input.parse::<kw::OriginalName>().unwrap();
+            input.parse::<kw::Colon>().unwrap();
+            return Ok(CacheOption::OriginalName(input.parse()?));
+        }
+        if la.peek(kw::HelperVisibility) {
+            input.parse::<kw::HelperVisibility>().unwrap();
+            input.parse::<kw::Colon>().unwrap();
+            return Ok(CacheOption::HelperVisibility(input.parse()?));
+        }
+        if la.peek(kw::NoOriginal) {
+            input.parse::<kw::NoOriginal>().unwrap();
+            return Ok(CacheOption::NoOriginal);
+        }
+        if la.peek(kw::HashKeys) {
+            input.parse::<kw::HashKeys>().unwrap();
+            // Verification of digests is optional.
+            if input.peek(kw::Colon) {
+                input.parse::<kw::Colon>().unwrap();
+                input.parse::<kw::Verify>()?;
+                return Ok(CacheOption::HashKeys { verify: true });
+            }
+            return Ok(CacheOption::HashKeys { verify: false });
+        }
+        if la.peek(kw::FloatKeys) {
+            input.parse::<kw::FloatKeys>().unwrap();
+            input.parse::<kw::Colon>().unwrap();
+            let la = input.lookahead1();
+            if la.peek(kw::Canonical) {
+                input.parse::<kw::Canonical>().unwrap();
+                return Ok(CacheOption::FloatKeys { bitwise: false });
+            }
+            if la.peek(kw::Bitwise) {
+                input.parse::<kw::Bitwise>().unwrap();
+                return Ok(CacheOption::FloatKeys { bitwise: true });
+            }
+            return Err(la.error());
+        }
+        if la.peek(kw::KeyWith) {
+            input.parse::<kw::KeyWith>().unwrap();
+            input.parse::<kw::Colon>().unwrap();
+            let arg = input.call(syn::Ident::parse_any)?;
+            input.parse::<syn::Token![=>]>()?;
+            return Ok(CacheOption::KeyWith(arg, input.parse()?));
+        }
+        if la.peek(kw::Key) {
+            input.parse::<kw::Key>().unwrap();
+            input.parse::<kw::Colon>().unwrap();
+            let key: syn::Expr = input.parse()?;
+            input.parse::<syn::Token![=>]>()?;
+            return Ok(CacheOption::Key(key, input.parse()?));
+        }
+        if la.peek(kw::PerInstance) {
+            input.parse::<kw::PerInstance>().unwrap();
+            // The name of the `InstanceCache` field is optional.
+            if input.peek(kw::Colon) {
+                input.parse::<kw::Colon>().unwrap();
+                return Ok(CacheOption::PerInstance(input.parse()?));
+            }
+            return Ok(CacheOption::PerInstance(syn::Ident::new(
+                "memoize_cache",
+                input.span(),
+            )));
+        }
         Err(la.error())
     }
 }
@@ -104,6 +230,20 @@ impl parse::Parse for CacheOptions {
                 CacheOption::HasherInit(init) => opts.custom_hasher_initializer = Some(init),
                 CacheOption::SharedCache => opts.shared_cache = true,
                 CacheOption::Ignore(ident) => opts.ignore.push(ident),
+                CacheOption::PerInstance(field) => opts.per_instance = Some(field),
+                CacheOption::Coalesce => opts.coalesce = true,
+                CacheOption::ReturnShared => opts.return_shared = true,
+                CacheOption::Leak => opts.leak = true,
+                CacheOption::OriginalName(name) => opts.original_name = Some(name),
+                CacheOption::HelperVisibility(vis) => opts.helper_visibility = Some(vis),
+                CacheOption::NoOriginal => opts.no_original = true,
+                CacheOption::Key(key, key_type) => opts.key = Some((key, key_type)),
+                CacheOption::KeyWith(arg, path) => opts.key_with.push((arg, path)),
+                CacheOption::FloatKeys { bitwise } => opts.bitwise_floats = bitwise,
+                CacheOption::HashKeys { verify } => {
+                    opts.hash_keys = true;
+                    opts.verify_keys = verify;
+                }
             }
         }
         Ok(opts)
@@ -143,6 +283,16 @@ mod store {
     ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
         (quote::quote! { insert }, quote::quote! { get })
     }
+
+    /// Returns the name of the method to remove an element from a store.
+    pub(crate) fn cache_remove_method(_options: &CacheOptions) -> proc_macro2::TokenStream {
+        quote::quote! { remove }
+    }
+
+    /// Returns the name of the method to get an element from a store without updating it.
+    pub(crate) fn cache_peek_method(_options: &CacheOptions) -> proc_macro2::TokenStream {
+        quote::quote! { get }
+    }
 }
 
 // This implementation of the storage backend also depends on the `lru` crate.
@@ -163,7 +313,10 @@ mod store {
     ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
         let value_type = match options.time_to_live {
             None => quote::quote! {#value_type},
-            Some(_) => quote::quote! {(std::time::Instant, #value_type)},
+            // The insertion time, and an individual time to live overriding `TimeToLive`.
+            Some(_) => quote::quote! {
+                (std::time::Instant, Option<std::time::Duration>, #value_type)
+            },
         };
         // This is the unbounded default.
         match options.lru_max_entries {
@@ -187,7 +340,7 @@ mod store {
                 )
             }
             Some(cap) => {
-                if let Some(_) = &options.custom_hasher {
+                if options.custom_hasher.is_some() {
                     (
                         quote::quote! { compile_error!("Cannot use LRU cache and a custom hasher at the same time") },
                         quote::quote! { std::collections::HashMap::new() },
@@ -195,7 +348,9 @@ mod store {
                 } else {
                     (
                         quote::quote! { ::memoize::lru::LruCache<#key_type, #value_type> },
-                        quote::quote! { ::memoize::lru::LruCache::new(#cap) },
+                        quote::quote! {
+                            ::memoize::lru::LruCache::new(::std::num::NonZeroUsize::new(#cap).unwrap())
+                        },
                     )
                 }
             }
@@ -213,16 +368,32 @@ mod store {
             Some(_) => (quote::quote! { put }, quote::quote! { get }),
         }
     }
+
+    /// Returns the name of the method to remove an element from a store.
+    pub(crate) fn cache_remove_method(options: &CacheOptions) -> proc_macro2::TokenStream {
+        match options.lru_max_entries {
+            None => quote::quote! { remove },
+            Some(_) => quote::quote! { pop },
+        }
+    }
+
+    /// Returns the name of the method to get an element from a store without updating it (in
+    /// particular, its recency in an LRU cache).
+    pub(crate) fn cache_peek_method(options: &CacheOptions) -> proc_macro2::TokenStream {
+        match options.lru_max_entries {
+            None => quote::quote! { get },
+            Some(_) => quote::quote! { peek },
+        }
+    }
 }
 
 /**
  * memoize is an attribute to create a memoized version of a (simple enough) function.
  *
- * So far, it works on non-method functions with one or more arguments returning a [`Clone`]-able
+ * So far, it works on functions with one or more arguments returning a [`Clone`]-able
  * value. Arguments that are cached must be [`Clone`]-able and [`Hash`]-able as well. Several clones
  * happen within the storage and recall layer, with the assumption being that `memoize` is used to
- * cache such expensive functions that very few `clone()`s do not matter. `memoize` doesn't work on
- * methods (functions with `[&/&mut/]self` receiver).
+ * cache such expensive functions that very few `clone()`s do not matter.
  *
  * Calls are memoized for the lifetime of a program, using a statically allocated, Mutex-protected
  * HashMap.
@@ -242,13 +413,104 @@ mod store {
  * assert!(! hello("World".to_string(), 0));
  * ```
  *
- * If you need to use the un-memoized function, it is always available as `memoized_original_{fn}`,
- * in this case: `memoized_original_hello()`.
+ * If you need to use the un-memoized function, it is available as `memoized_original_{fn}`, in this
+ * case: `memoized_original_hello()`. It is hidden from the documentation, unless it is named
+ * explicitly using `OriginalName: raw_hello`. With `NoOriginal`, it isn't accessible at all (inside
+ * an `impl` block, it becomes private).
+ *
+ * The helper functions described below have the same visibility as the memoized function by
+ * default; a different one can be specified with e.g. `HelperVisibility: pub(crate)` (or
+ * `HelperVisibility: pub(self)` for private helpers).
+ *
+ * Arguments passed by shared reference, such as `&str`, `&[T]` or `&Path`, are stored in the cache
+ * in their owned form (`String`, `Vec<T>`, `PathBuf`) using [`ToOwned`]; the referenced type must
+ * then be [`Hash`] and [`Eq`]. A borrowed argument is only converted when a value is inserted.
+ * References `&'static T` to other types are stored as they are, so `T` needn't be [`Clone`].
+ *
+ * Lookups don't clone or convert the arguments, but borrow the stored keys for comparison, so that
+ * cache hits don't allocate. On a miss, the arguments are cloned once to call the original
+ * function, and then moved into the key. Keys of more than eight arguments are cloned for lookups.
+ *
+ * Arguments of type `f32` and `f64` (or shared references to them) are stored by their bit pattern,
+ * as a `memoize::FloatKey`. By default (`FloatKeys: Canonical`), all NaNs are the same key, and so
+ * are `-0.0` and `0.0`; with `FloatKeys: Bitwise`, the bit patterns are used as they are. A `KeyWith`
+ * function for such an argument returns a `FloatKey`, too, e.g. `FloatKey::canonical(&x.round())`.
+ * Floating-point numbers inside of other argument types aren't recognized.
+ *
+ * Arguments can be destructured by tuple, struct and tuple struct patterns, such as
+ * `(x, y): (i32, i32)`; the whole argument is then part of the key. Such arguments can't be
+ * `Ignore`d, as they have no name.
  *
  * Parameters can be ignored by the cache using the `Ignore` parameter. `Ignore` can be specified
  * multiple times, once per each parameter. `Ignore`d parameters do not need to implement [`Clone`]
- * or [`Hash`]. 
- * 
+ * or [`Hash`].
+ *
+ * If only part of the arguments identifies the result, the key can be given as an expression
+ * over the arguments together with its type, e.g. `Key: user.id => u64` or
+ * `Key: (user.id, lang.to_lowercase()) => (u64, String)`. The arguments then don't need to be
+ * [`Clone`] or [`Hash`], but the key expression mustn't move out of them, as it is evaluated before
+ * they are passed to the original function. The helper functions operating on a single entry take
+ * the key instead of the arguments.
+ *
+ * Individual arguments can be normalized before they are used as (part of) the key with
+ * `KeyWith: arg => path::to::function`, e.g. to ignore the case of host names. The function is
+ * called once per call with a reference to the argument (or the argument itself, if it is a
+ * shared reference), and returns its key, of the type the argument would otherwise be stored as.
+ * The original function still receives the argument unchanged. `KeyWith` can be specified once per
+ * argument, but not together with `Key`.
+ *
+ * For large arguments, `HashKeys` stores a 128-bit digest of the key in the cache instead of the
+ * key itself. Memoized arguments then only need to be [`Hash`], as they are passed on to the
+ * original function instead of being cloned into the key. Arguments with different digests are
+ * never confused, but different arguments with the same digest would be; with `HashKeys: Verify`,
+ * a fingerprint of the key computed by a different hash function is stored, too, so that such a
+ * collision is a cache miss instead.
+ *
+ * Methods with a `self` or `&self` receiver can be memoized, too. The receiver is then part of the
+ * key, requiring `Self` to be [`Clone`] and [`Hash`]; alternatively, it can be excluded from the
+ * key with `Ignore: self`. `&mut self` methods can only be memoized if `self` is ignored. As the
+ * cache of a method is stored type-erased, `Self` and all argument and return types must be
+ * `'static`. The generated helper functions (see below) are associated functions, e.g.
+ * `Type::memoized_flush_method()`.
+ *
+ * Associated functions without a receiver can only be recognized if their signature mentions
+ * `Self`. Otherwise, place `#[memoize]` on the (inherent) `impl` block: then all associated
+ * functions marked with `#[memoize]` or `#[memoize(...)]` are memoized.
+ *
+ * With `#[memoize(PerInstance)]`, a method's cache is instead stored in a field of type
+ * `memoize::InstanceCache` of the receiver, named `memoize_cache` (a different name can be given
+ * as `PerInstance: field_name`). The receiver is then not part of the key, and the cached values
+ * are dropped together with the instance. The helper functions take `&self` in that case. Cached
+ * argument and return types must be `Send`.
+ *
+ * Generic functions are memoized with a separate cache for every instantiation. As these caches
+ * are stored type-erased, all type parameters must be `'static` (and `Send`, with `SharedCache`).
+ * The helper functions are not generic, and operate on the caches of all instantiations.
+ *
+ * `async fn`s can be memoized as well; in that case, the output of the awaited future is cached.
+ * The cache is not locked while the original function is awaited, so concurrent calls with the
+ * same arguments may each call it. To avoid this, specify `Coalesce`: then only the first caller
+ * missing a key calls the original function, and concurrent callers with the same key await its
+ * result. If that caller is cancelled or panics, one of the waiting callers takes over. `Coalesce`
+ * implies `SharedCache` (unless used with `PerInstance`).
+ *
+ * Return values are cloned whenever they are stored in or returned from the cache. If that is
+ * expensive, or the return type doesn't implement [`Clone`] at all, specify `ReturnShared`: values
+ * are then stored as `Arc<T>` (or `Rc<T>` for thread-local caches) instead of `T`, and the memoized
+ * function returns that pointer. `memoized_original_{fn}` still returns `T`.
+ *
+ * For interning, `Leak` allocates every computed value once and leaks it; the memoized function
+ * then returns a `&'static T`, so results for the same arguments can be compared by address.
+ * `Leak` implies `SharedCache` (unless used with `PerInstance`), so `T` must be [`Sync`]. Leaked
+ * values are never freed: flushing the cache (or an expired `TimeToLive`) only forgets the
+ * references, and the next call leaks a new value.
+ *
+ * Attributes of the memoized function are handled as follows: `#[cfg]` applies to all generated
+ * items; documentation, `#[must_use]`, `#[inline]`, `#[deprecated]`, `#[no_mangle]` and
+ * `#[export_name]` apply to the memoized function; lint levels (`#[allow]`, `#[warn]`, `#[deny]`,
+ * `#[forbid]`) apply to the memoized, the original and the helper functions; all other attributes
+ * stay on `memoized_original_{fn}`. `unsafe fn`s and `extern "C" fn`s can be memoized, `const fn`s can't.
+ *
  * See the `examples` for concrete applications.
  *
  * *The following descriptions need the `full` feature enabled.*
@@ -265,31 +527,202 @@ mod store {
  * This mechanism can, in principle, be extended (in the source code) to any other cache mechanism.
  *
  * `memoized_flush_<function name>()` allows you to clear the underlying memoization cache of a
- * function. This function is generated with the same visibility as the memoized function.
+ * function. Like the other helper functions, it has the visibility of the memoized function, unless
+ * `HelperVisibility` specifies another one.
+ *
+ * `memoized_invalidate_<function name>(args...)` removes the entry for the given memoized (not
+ * `Ignore`d) arguments from the cache, and returns its value if it hasn't expired. For methods whose
+ * receiver is part of the key (or holds a `PerInstance` cache), it takes the receiver as well; for
+ * generic functions, it is generic, too.
+ *
+ * `memoized_peek_<function name>(args...)` returns the cached value for the given arguments, if it
+ * exists and hasn't expired, without calling the original function. It doesn't count as a use in an
+ * LRU cache (`Capacity`); for those, `memoized_get_<function name>(args...)` does the same, but marks
+ * the entry as most recently used.
+ *
+ * `memoized_refresh_<function name>(args...)` takes the same arguments as the memoized function,
+ * but always calls the original function, and replaces the cached value with the result (taking
+ * the lock of a `SharedCache` once, after the call).
+ *
+ * `memoized_prime_<function name>(args..., value)` inserts a value for the given arguments without
+ * calling the original function. With `TimeToLive`, `memoized_prime_<function name>_with_ttl(args...,
+ * value, time_to_live)` additionally overrides the time to live of that entry.
+ *
+ * `<function name>_with_status(args...)` is a variant of the memoized function that also returns
+ * a `memoize::CacheStatus`: whether the value was cached (`Hit`, with the age of the value if
+ * `TimeToLive` is used), not cached (`Miss`), or cached but expired (`Expired`). It has the
+ * visibility of the helper functions.
+ *
+ * `memoized_stats_<function name>()` returns a `memoize::CacheStats` with the numbers of hits,
+ * misses, insertions, expired entries, LRU evictions and flushes of the cache since the start of the
+ * program. The counters are atomic and shared by all threads (and all instances, for `PerInstance`
+ * caches), so counting doesn't take a lock.
+ *
+ * A handle to the cache of a function `hello` is available as `HELLO_CACHE`, a
+ * `memoize::CacheHandle` implementing the `memoize::CacheControl` trait, so that caches can be
+ * managed generically (e.g. through `&dyn CacheControl`). Inside an `impl` block, the handle is an
+ * associated constant (`Type::HELLO_CACHE`). It has the visibility of the helper functions.
+ *
+ * On their first call or priming, memoized functions register their handle in a global registry:
+ * `memoize::caches()` returns the handles of all registered functions, and `memoize::flush_all()`
+ * clears their caches. Thread-local caches are only cleared on the current thread. Methods with
+ * `PerInstance` caches are not registered.
  *
  */
 #[proc_macro_attribute]
 pub fn memoize(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // On an `impl` block, the methods marked with `#[memoize]` are memoized.
+    if let Ok(item_impl) = syn::parse::<syn::ItemImpl>(item.clone()) {
+        return memoize_impl(attr, item_impl).into();
+    }
     let func = parse_macro_input!(item as ItemFn);
+    let options = parse_macro_input!(attr as CacheOptions);
+    memoize_fn(options, func, false).into()
+}
+
+/// Memoizes the methods of an inherent `impl` block that are marked with `#[memoize]`.
+fn memoize_impl(attr: TokenStream, mut item_impl: syn::ItemImpl) -> proc_macro2::TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::TokenStream::from(attr).span(),
+            "Options of an `impl` block's memoized methods must be given on the methods!",
+        )
+        .to_compile_error();
+    }
+    if let Some((_, path, _)) = &item_impl.trait_ {
+        return syn::Error::new(
+            path.span(),
+            "Only inherent `impl` blocks can be memoized, as the helper functions can't be added to a trait implementation!",
+        )
+        .to_compile_error();
+    }
+
+    for item in &mut item_impl.items {
+        let expanded = match item {
+            syn::ImplItem::Method(method) => {
+                let pos = method
+                    .attrs
+                    .iter()
+                    .position(|a| a.path.segments.last().is_some_and(|s| s.ident == "memoize"));
+                pos.map(|pos| {
+                    let attr = method.attrs.remove(pos);
+                    let options = if attr.tokens.is_empty() {
+                        Ok(CacheOptions::default())
+                    } else {
+                        attr.parse_args::<CacheOptions>()
+                    };
+                    let func = ItemFn {
+                        attrs: method.attrs.clone(),
+                        vis: method.vis.clone(),
+                        sig: method.sig.clone(),
+                        block: Box::new(method.block.clone()),
+                    };
+                    match options {
+                        Ok(options) => memoize_fn(options, func, true),
+                        Err(e) => e.to_compile_error(),
+                    }
+                })
+            }
+            _ => None,
+        };
+        if let Some(expanded) = expanded {
+            *item = syn::ImplItem::Verbatim(expanded);
+        }
+    }
+    item_impl.to_token_stream()
+}
+
+/// Returns whether `tokens` mention `Self`, i.e. are part of an `impl` block.
+fn mentions_self(tokens: proc_macro2::TokenStream) -> bool {
+    tokens.into_iter().any(|t| match t {
+        proc_macro2::TokenTree::Ident(ident) => ident == "Self",
+        proc_macro2::TokenTree::Group(group) => mentions_self(group.stream()),
+        _ => false,
+    })
+}
+
+/// Memoizes `func`. If `associated` is set, `func` is an associated function in an `impl` block.
+fn memoize_fn(
+    mut options: CacheOptions,
+    func: ItemFn,
+    associated: bool,
+) -> proc_macro2::TokenStream {
     let sig = &func.sig;
 
     let fn_name = &sig.ident.to_string();
-    let renamed_name = format!("memoized_original_{}", fn_name);
+    let renamed_name = match &options.original_name {
+        Some(name) => name.to_string(),
+        None => format!("memoized_original_{}", fn_name),
+    };
     let flush_name = syn::Ident::new(format!("memoized_flush_{}", fn_name).as_str(), sig.span());
     let size_name = syn::Ident::new(format!("memoized_size_{}", fn_name).as_str(), sig.span());
+    let invalidate_name = syn::Ident::new(&format!("memoized_invalidate_{}", fn_name), sig.span());
+    let prime_name = syn::Ident::new(&format!("memoized_prime_{}", fn_name), sig.span());
+    let peek_name = syn::Ident::new(&format!("memoized_peek_{}", fn_name), sig.span());
+    let stats_name = syn::Ident::new(&format!("memoized_stats_{}", fn_name), sig.span());
     let map_name = format!("memoized_mapping_{}", fn_name);
+    let handle_name = syn::Ident::new(&format!("{}_CACHE", fn_name.to_uppercase()), sig.span());
+
+    // Associated functions are expanded inside an `impl` block, where we can only generate
+    // associated items. Besides those in memoized `impl` blocks, we recognize methods and functions
+    // whose signature mentions `Self`.
+    let in_impl = associated || sig.receiver().is_some() || mentions_self(sig.to_token_stream());
+    // Generic functions need a separate cache per instantiation.
+    let generic_args: Vec<_> = sig
+        .generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            syn::GenericParam::Type(t) => Some(t.ident.clone()),
+            syn::GenericParam::Const(c) => Some(c.ident.clone()),
+            syn::GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+    // Caches whose type may mention `Self` or generic parameters can't be stored in a static.
+    let erased = in_impl || !generic_args.is_empty();
 
-    if let Some(syn::FnArg::Receiver(_)) = sig.inputs.first() {
-        return quote::quote! { compile_error!("Cannot memoize methods!"); }.into();
+    if options.coalesce {
+        if sig.asyncness.is_none() {
+            return syn::Error::new(sig.span(), "Coalesce can only be used on async functions!")
+                .to_compile_error();
+        }
+        // Computations in flight are shared between threads, so the cache has to be, too.
+        if options.per_instance.is_none() {
+            options.shared_cache = true;
+        }
+    }
+
+    if let Some(constness) = &sig.constness {
+        return syn::Error::new(
+            constness.span(),
+            "Cannot memoize `const fn`s, as the cache can't be accessed in constant evaluation!",
+        )
+        .to_compile_error();
+    }
+
+    if let (Some(name), true) = (&options.original_name, options.no_original) {
+        return syn::Error::new(
+            name.span(),
+            "OriginalName and NoOriginal can't be used together!",
+        )
+        .to_compile_error();
     }
 
-    // Parse options from macro attributes
-    let options: CacheOptions = syn::parse(attr.clone()).unwrap();
+    if options.leak {
+        if options.return_shared {
+            return syn::Error::new(sig.span(), "Leak and ReturnShared can't be used together!")
+                .to_compile_error();
+        }
+        // Leaked values are available to all threads, so they are stored in a shared cache.
+        if options.per_instance.is_none() {
+            options.shared_cache = true;
+        }
+    }
 
     // Extracted from the function signature.
     let input_params = match check_signature(sig, &options) {
         Ok(p) => p,
-        Err(e) => return e.to_compile_error().into(),
+        Err(e) => return e.to_compile_error(),
     };
 
     // Input types and names that are actually stored in the cache.
@@ -297,30 +730,52 @@ pub fn memoize(attr: TokenStream, item: TokenStream) -> TokenStream {
         .iter()
         .filter_map(|p| {
             if p.is_memoized {
-                Some(p.arg_type.clone())
+                Some(p.key_type.clone())
             } else {
                 None
             }
         })
         .collect();
-    let memoized_input_names: Vec<syn::Ident> = input_params
+
+    // For each cached input, expression to be moved into the key. Borrowed arguments are
+    // converted into owned values, and normalized arguments are replaced by their key, which is
+    // computed once by the key prelude.
+    let memoized_key_exprs: Vec<_> = input_params
         .iter()
-        .filter_map(|p| {
-            if p.is_memoized {
-                Some(p.arg_name.clone())
+        .filter(|p| p.is_memoized)
+        .map(|p| {
+            let ident = p.key_ident();
+            if p.normalizer.is_some() {
+                quote::quote! { #ident }
+            } else if p.is_borrowed {
+                quote::quote! { ::std::borrow::ToOwned::to_owned(#ident) }
             } else {
-                None
+                quote::quote! { #ident }
+            }
+        })
+        .collect();
+    // Same, but without consuming the arguments.
+    let memoized_cloned_exprs: Vec<_> = input_params
+        .iter()
+        .filter(|p| p.is_memoized)
+        .map(|p| {
+            let ident = p.key_ident();
+            if p.is_borrowed && p.normalizer.is_none() {
+                quote::quote! { ::std::borrow::ToOwned::to_owned(#ident) }
+            } else {
+                quote::quote! { #ident.clone() }
             }
         })
         .collect();
 
     // For each input, expression to be passe through to the original function.
-    // Cached arguments are cloned, original arguments are forwarded as-is
+    // Cached arguments are cloned, original and borrowed arguments are forwarded as-is. With
+    // `HashKeys`, the key is computed beforehand, so all arguments are forwarded as-is.
     let fn_forwarded_exprs: Vec<_> = input_params
         .iter()
         .map(|p| {
             let ident = p.arg_name.clone();
-            if p.is_memoized {
+            if p.is_memoized && !p.is_borrowed && !options.hash_keys {
                 quote::quote! { #ident.clone() }
             } else {
                 quote::quote! { #ident }
@@ -328,135 +783,963 @@ pub fn memoize(attr: TokenStream, item: TokenStream) -> TokenStream {
         })
         .collect();
 
-    let input_tuple_type = quote::quote! { (#(#memoized_input_types),*) };
+    // With `Key`, the cache is keyed by the value of the key expression instead of the arguments.
+    // With `HashKeys`, it is keyed by the digest of the key.
+    let input_tuple_type = match (&options.key, options.hash_keys, options.verify_keys) {
+        (_, true, false) => quote::quote! { u128 },
+        (_, true, true) => quote::quote! { (u128, u64) },
+        (Some((_, key_type)), false, _) => key_type.to_token_stream(),
+        (None, false, _) => quote::quote! { (#(#memoized_input_types),*) },
+    };
     let return_type = match &sig.output {
         syn::ReturnType::Default => quote::quote! { () },
         syn::ReturnType::Type(_, ty) => ty.to_token_stream(),
     };
+    // With `ReturnShared`, return values are stored and returned behind a shared pointer, which is
+    // cheap to clone.
+    let shared_ptr = if options.shared_cache || options.per_instance.is_some() {
+        quote::quote! { std::sync::Arc }
+    } else {
+        quote::quote! { std::rc::Rc }
+    };
+    let mut wrapper_sig = sig.clone();
+    // The memoized function binds destructured arguments to their hidden names.
+    for (input, param) in wrapper_sig.inputs.iter_mut().zip(&input_params) {
+        if let syn::FnArg::Typed(arg) = input {
+            if !matches!(*arg.pat, syn::Pat::Ident(_)) {
+                let arg_name = &param.arg_name;
+                *arg.pat = syn::parse_quote! { #arg_name };
+            }
+        }
+    }
+    let return_type = if options.return_shared {
+        let shared_type = quote::quote! { #shared_ptr<#return_type> };
+        wrapper_sig.output = syn::parse_quote! { -> #shared_type };
+        shared_type
+    } else if options.leak {
+        // With `Leak`, every value is leaked once, and references to it are stored and returned.
+        let leaked_type = quote::quote! { &'static #return_type };
+        wrapper_sig.output = syn::parse_quote! { -> #leaked_type };
+        leaked_type
+    } else {
+        return_type
+    };
 
     // Construct storage for the memoized keys and return values.
     let store_ident = syn::Ident::new(&map_name.to_uppercase(), sig.span());
     let (cache_type, cache_init) =
-        store::construct_cache(&options, input_tuple_type, return_type.clone());
-    let store = if options.shared_cache {
+        store::construct_cache(&options, input_tuple_type.clone(), return_type.clone());
+    // With `Coalesce`, computations in flight are stored next to the cache.
+    let (cache_type, cache_init) = if options.coalesce {
+        (
+            quote::quote! { ::memoize::coalesce::Coalesced<#cache_type, #input_tuple_type, #return_type> },
+            quote::quote! { ::memoize::coalesce::Coalesced::new(#cache_init) },
+        )
+    } else {
+        (cache_type, cache_init)
+    };
+    let store_type = match (erased, options.shared_cache) {
+        (false, false) => quote::quote! { std::cell::RefCell<#cache_type> },
+        (false, true) => quote::quote! { std::sync::Mutex<#cache_type> },
+        (true, false) => quote::quote! {
+            std::cell::RefCell<::memoize::erased::TypeMap<dyn ::memoize::erased::ErasedCache>>
+        },
+        (true, true) => quote::quote! {
+            std::sync::Mutex<::memoize::erased::TypeMap<dyn ::memoize::erased::ErasedCache + Send>>
+        },
+    };
+    let store_init = if erased {
+        quote::quote! { ::memoize::erased::TypeMap::new() }
+    } else {
+        cache_init.clone()
+    };
+    let store_item = if options.shared_cache {
         quote::quote! {
             ::memoize::lazy_static::lazy_static! {
-                static ref #store_ident : std::sync::Mutex<#cache_type> =
-                    std::sync::Mutex::new(#cache_init);
+                static ref #store_ident : #store_type = std::sync::Mutex::new(#store_init);
             }
         }
     } else {
         quote::quote! {
             std::thread_local! {
-                static #store_ident : std::cell::RefCell<#cache_type> =
-                    std::cell::RefCell::new(#cache_init);
+                static #store_ident : #store_type = std::cell::RefCell::new(#store_init);
+            }
+        }
+    };
+
+    // Inside an `impl` block, the store can't be a module-level static. Instead, it is hidden in an
+    // associated function returning a reference to it.
+    let (store, store_ref) = if in_impl {
+        let map_ident = syn::Ident::new(&map_name, sig.span());
+        let store_ref_type = if options.shared_cache {
+            quote::quote! { #store_type }
+        } else {
+            quote::quote! { std::thread::LocalKey<#store_type> }
+        };
+        (
+            quote::quote! {
+                #[doc(hidden)]
+                fn #map_ident() -> &'static #store_ref_type {
+                    #store_item
+                    &#store_ident
+                }
+            },
+            quote::quote! { Self::#map_ident() },
+        )
+    } else {
+        (store_item, quote::quote! { #store_ident })
+    };
+    // Per-instance caches live in an `InstanceCache` field of `self` instead.
+    let (store, store_ref) = if let Some(field) = &options.per_instance {
+        (quote::quote! {}, quote::quote! { self.#field })
+    } else {
+        (store, store_ref)
+    };
+    let locked = options.shared_cache || options.per_instance.is_some();
+
+    // Statistics are counted in atomics next to the store, shared by all threads and instances.
+    let counters_name = format!("memoized_counters_{}", fn_name);
+    let counters_ident = syn::Ident::new(&counters_name.to_uppercase(), sig.span());
+    let counters_item = quote::quote! {
+        static #counters_ident: ::memoize::stats::Counters = ::memoize::stats::Counters::new();
+    };
+    let (counters, counters_ref) = if in_impl {
+        let counters_fn = syn::Ident::new(&counters_name, sig.span());
+        (
+            quote::quote! {
+                #[doc(hidden)]
+                fn #counters_fn() -> &'static ::memoize::stats::Counters {
+                    #counters_item
+                    &#counters_ident
+                }
+            },
+            quote::quote! { Self::#counters_fn() },
+        )
+    } else {
+        (counters_item, quote::quote! { #counters_ident })
+    };
+
+    // Attributes of the original function are distributed among the generated items: `cfg`s apply
+    // to all of them, documentation and attributes concerning the interface or linkage of the
+    // function move to the memoized function, and lint levels apply to all generated functions.
+    // All other attributes stay on the original function.
+    let mut cfg_attrs = vec![];
+    let mut lint_attrs = vec![];
+    let mut wrapper_attrs = vec![];
+    let mut original_attrs = vec![];
+    for attr in &func.attrs {
+        let name = attr
+            .path
+            .get_ident()
+            .map(|i| i.to_string())
+            .unwrap_or_default();
+        match name.as_str() {
+            "cfg" => {
+                cfg_attrs.push(attr.clone());
+                original_attrs.push(attr.clone());
+            }
+            "doc" | "must_use" | "inline" | "deprecated" | "no_mangle" | "export_name" => {
+                wrapper_attrs.push(attr.clone())
+            }
+            "allow" | "warn" | "deny" | "forbid" => {
+                lint_attrs.push(attr.clone());
+                wrapper_attrs.push(attr.clone());
+                original_attrs.push(attr.clone());
             }
+            _ => original_attrs.push(attr.clone()),
         }
+    }
+
+    // Links to the memoized function in the documentation of generated functions.
+    let fn_link = if in_impl {
+        format!("[`Self::{}`]", fn_name)
+    } else {
+        format!("[`{}`]", fn_name)
     };
 
-    // Rename original function.
+    // Rename original function. It is only documented if it was named explicitly.
     let mut renamed_fn = func.clone();
+    renamed_fn.attrs = original_attrs;
     renamed_fn.sig.ident = syn::Ident::new(&renamed_name, func.sig.span());
+    if options.original_name.is_some() {
+        let doc = format!("Calls {} without memoization.", fn_link);
+        renamed_fn.attrs.push(syn::parse_quote! { #[doc = #doc] });
+    } else {
+        renamed_fn.attrs.push(syn::parse_quote! { #[doc(hidden)] });
+    }
+    // Without the original function, it is nested in the memoized function. This isn't possible
+    // in an `impl` block, where it is made private instead.
+    if options.no_original {
+        renamed_fn.vis = syn::Visibility::Inherited;
+    }
     let memoized_id = &renamed_fn.sig.ident;
+    let turbofish = if generic_args.is_empty() {
+        quote::quote! {}
+    } else {
+        quote::quote! { ::<#(#generic_args),*> }
+    };
+    let memoized_path = if in_impl {
+        quote::quote! { Self::#memoized_id #turbofish }
+    } else {
+        quote::quote! { #memoized_id #turbofish }
+    };
+
+    // If the cache type can't be named in a static, the cache is stored type-erased, with one cache
+    // per instantiation of the memoized function, and selected by `select_cache`. Referring to the
+    // memoized function there mustn't warn if it is `#[deprecated]`.
+    let fn_ident = &sig.ident;
+    let fn_path = if in_impl {
+        quote::quote! { Self::#fn_ident #turbofish }
+    } else {
+        quote::quote! { #fn_ident #turbofish }
+    };
+    let erased_cache = quote::quote! {
+        get_or_insert_with::<#cache_type>(::memoize::erased::type_id_of(&#fn_path), || {
+            Box::new(::memoize::erased::Erased::<#cache_type>::new(#cache_init, |c| c.len()))
+        })
+    };
+    let select_cache = if options.per_instance.is_some() {
+        // Per-instance caches have one type-erased store per method.
+        quote::quote! {
+            #[allow(deprecated)]
+            let ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.entry(#fn_name).or_default().#erased_cache;
+        }
+    } else if erased {
+        quote::quote! {
+            #[allow(deprecated)]
+            let ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.#erased_cache;
+        }
+    } else {
+        quote::quote! {}
+    };
 
     // Construct memoizer function, which calls the original function.
-    let syntax_names_tuple = quote::quote! { (#(#memoized_input_names),*) };
-    let syntax_names_tuple_cloned = quote::quote! { (#(#memoized_input_names.clone()),*) };
+    let syntax_names_tuple = quote::quote! { (#(#memoized_key_exprs),*) };
+    let syntax_names_tuple_cloned = quote::quote! { (#(#memoized_cloned_exprs),*) };
+    // Arguments are looked up without cloning them (or converting borrowed arguments) into an owned
+    // key, by borrowing the stored keys: directly for a single argument, and as a `KeyView` for
+    // several ones.
+    let memoized_params: Vec<&FnArgument> = input_params.iter().filter(|p| p.is_memoized).collect();
+    let lookup_refs: Vec<_> = memoized_params
+        .iter()
+        .map(|p| {
+            let ident = p.key_ident();
+            if p.is_borrowed && p.normalizer.is_none() {
+                quote::quote! { #ident }
+            } else {
+                quote::quote! { &#ident }
+            }
+        })
+        .collect();
+    let lookup_ref_types: Vec<_> = memoized_params.iter().map(|p| &p.ref_type).collect();
+    let lookup_key = match memoized_params.len() {
+        0 => quote::quote! { &() },
+        1 => quote::quote! { #(#lookup_refs)* },
+        n if n <= MAX_KEY_VIEW_ARITY => {
+            let view = syn::Ident::new(&format!("KeyView{}", n), sig.span());
+            quote::quote! {
+                &(#(#lookup_refs),*) as &dyn ::memoize::keys::#view<#(#lookup_ref_types),*>
+            }
+        }
+        _ => quote::quote! { &#syntax_names_tuple_cloned },
+    };
+    // A custom key or digest is computed before the arguments are passed on to the original
+    // function, and bound to `ATTR_MEMOIZE_CUSTOM_KEY__`.
+    let digest_fn = if options.verify_keys {
+        quote::quote! { ::memoize::digest::verified_digest }
+    } else {
+        quote::quote! { ::memoize::digest::digest }
+    };
+    let digest = |key: proc_macro2::TokenStream| {
+        if options.hash_keys {
+            quote::quote! { let ATTR_MEMOIZE_CUSTOM_KEY__ = #digest_fn(#key); }
+        } else {
+            quote::quote! {}
+        }
+    };
+    // Likewise, the keys of normalized arguments are computed once, and bound to
+    // `ATTR_MEMOIZE_KEY_<arg>__`.
+    let key_prelude = match &options.key {
+        Some((key, key_type)) => {
+            let digest = digest(quote::quote! { &ATTR_MEMOIZE_CUSTOM_KEY__ });
+            quote::quote! {
+                let ATTR_MEMOIZE_CUSTOM_KEY__: #key_type = #key;
+                #digest
+            }
+        }
+        None => {
+            let normalized = memoized_params.iter().filter_map(|p| p.normalized());
+            let digest = digest(quote::quote! { &(#(#lookup_refs),*) });
+            quote::quote! {
+                #(#normalized)*
+                #digest
+            }
+        }
+    };
+    let (syntax_names_tuple, syntax_names_tuple_cloned, lookup_key) =
+        if options.key.is_some() || options.hash_keys {
+            (
+                quote::quote! { ATTR_MEMOIZE_CUSTOM_KEY__ },
+                quote::quote! { ::std::clone::Clone::clone(&ATTR_MEMOIZE_CUSTOM_KEY__) },
+                quote::quote! { &ATTR_MEMOIZE_CUSTOM_KEY__ },
+            )
+        } else {
+            (syntax_names_tuple, syntax_names_tuple_cloned, lookup_key)
+        };
     let forwarding_tuple = quote::quote! { (#(#fn_forwarded_exprs),*) };
+    // An `async fn` is awaited, so that the output is cached instead of the future. The store is
+    // never borrowed or locked across the `.await`.
+    let call_original = if sig.unsafety.is_some() {
+        quote::quote! { unsafe { #memoized_path #forwarding_tuple } }
+    } else {
+        quote::quote! { #memoized_path #forwarding_tuple }
+    };
+    let call_original = if sig.asyncness.is_some() {
+        quote::quote! { #call_original.await }
+    } else {
+        call_original
+    };
+    let call_original = if options.return_shared {
+        quote::quote! { #shared_ptr::new(#call_original) }
+    } else if options.leak {
+        quote::quote! { &*::std::boxed::Box::leak(::std::boxed::Box::new(#call_original)) }
+    } else {
+        call_original
+    };
     let (insert_fn, get_fn) = store::cache_access_methods(&options);
-    let (read_memo, memoize) = match options.time_to_live {
-        None => (
-            quote::quote!(ATTR_MEMOIZE_HM__.#get_fn(&#syntax_names_tuple_cloned).cloned()),
-            quote::quote!(ATTR_MEMOIZE_HM__.#insert_fn(#syntax_names_tuple, ATTR_MEMOIZE_RETURN__.clone());),
-        ),
-        Some(ttl) => (
-            quote::quote! {
-                ATTR_MEMOIZE_HM__.#get_fn(&#syntax_names_tuple_cloned).and_then(|(last_updated, ATTR_MEMOIZE_RETURN__)|
-                    (last_updated.elapsed() < #ttl).then(|| ATTR_MEMOIZE_RETURN__.clone())
+    // Entry stored for `value`; with `TimeToLive`, `entry_ttl` may override its time to live.
+    let new_entry =
+        |value: proc_macro2::TokenStream, entry_ttl: proc_macro2::TokenStream| match &options
+            .time_to_live
+        {
+            None => value,
+            Some(_) => quote::quote! { (std::time::Instant::now(), #entry_ttl, #value) },
+        };
+    let is_fresh = match &options.time_to_live {
+        None => quote::quote! { true },
+        Some(ttl) => quote::quote! { last_updated.elapsed() < entry_ttl.unwrap_or(#ttl) },
+    };
+    let memoize_entry = new_entry(
+        quote::quote! { ::std::clone::Clone::clone(&ATTR_MEMOIZE_RETURN__) },
+        quote::quote! { None },
+    );
+    // Counts an insertion for the arguments, and the eviction it causes in a full LRU cache.
+    let count_insert = if options.lru_max_entries.is_some() {
+        quote::quote! {
+            if ATTR_MEMOIZE_HM__.len() == ATTR_MEMOIZE_HM__.cap().get()
+                && !ATTR_MEMOIZE_HM__.contains(#lookup_key)
+            {
+                #counters_ref.eviction();
+            }
+            #counters_ref.insert();
+        }
+    } else {
+        quote::quote! { #counters_ref.insert(); }
+    };
+    let memoize = quote::quote! {
+        #count_insert
+        ATTR_MEMOIZE_HM__.#insert_fn(#syntax_names_tuple, #memoize_entry);
+    };
+    // Reads the fresh value for the arguments from the store using the method `read_fn`.
+    // `on_fresh` and `on_expired` are evaluated when a fresh or expired entry is found.
+    let read_with = |read_fn: &proc_macro2::TokenStream,
+                     on_fresh: proc_macro2::TokenStream,
+                     on_expired: proc_macro2::TokenStream| {
+        match &options.time_to_live {
+            None if on_fresh.is_empty() => {
+                quote::quote!(ATTR_MEMOIZE_HM__.#read_fn(#lookup_key).cloned())
+            }
+            None => quote::quote! {
+                ATTR_MEMOIZE_HM__.#read_fn(#lookup_key).map(|ATTR_MEMOIZE_RETURN__| {
+                    #on_fresh
+                    ::std::clone::Clone::clone(ATTR_MEMOIZE_RETURN__)
+                })
+            },
+            Some(_) => quote::quote! {
+                ATTR_MEMOIZE_HM__.#read_fn(#lookup_key).and_then(|(last_updated, entry_ttl, ATTR_MEMOIZE_RETURN__)|
+                    if #is_fresh {
+                        #on_fresh
+                        Some(::std::clone::Clone::clone(ATTR_MEMOIZE_RETURN__))
+                    } else {
+                        #on_expired
+                        None
+                    }
                 )
             },
-            quote::quote!(ATTR_MEMOIZE_HM__.#insert_fn(#syntax_names_tuple, (std::time::Instant::now(), ATTR_MEMOIZE_RETURN__.clone()));),
-        ),
+        }
     };
+    let count_hit = quote::quote! { #counters_ref.hit(); };
+    let count_miss = quote::quote! { #counters_ref.miss(); };
+    let count_expired = quote::quote! { #counters_ref.expiration(); };
 
-    let memoizer = if options.shared_cache {
-        quote::quote! {
-            {
-                let mut ATTR_MEMOIZE_HM__ = #store_ident.lock().unwrap();
-                if let Some(ATTR_MEMOIZE_RETURN__) = #read_memo {
-                    return ATTR_MEMOIZE_RETURN__
+    // Generates the body of the memoized function. With `with_status`, the `CacheStatus` of the
+    // call is tracked in `ATTR_MEMOIZE_STATUS__`, and returned together with the value.
+    let make_memoizer = |with_status: bool| {
+        let (init_status, on_fresh, on_expired, on_joined) = if with_status {
+            let (hit_age, joined_age) = match &options.time_to_live {
+                None => (quote::quote! { None }, quote::quote! { None }),
+                Some(_) => (
+                    quote::quote! { Some(last_updated.elapsed()) },
+                    quote::quote! { Some(::std::time::Duration::ZERO) },
+                ),
+            };
+            (
+                quote::quote! { let mut ATTR_MEMOIZE_STATUS__ = ::memoize::CacheStatus::Miss; },
+                quote::quote! {
+                    ATTR_MEMOIZE_STATUS__ = ::memoize::CacheStatus::Hit { age: #hit_age };
+                },
+                quote::quote! {
+                    #count_expired
+                    ATTR_MEMOIZE_STATUS__ = ::memoize::CacheStatus::Expired;
+                },
+                quote::quote! {
+                    ATTR_MEMOIZE_STATUS__ = ::memoize::CacheStatus::Hit { age: #joined_age };
+                },
+            )
+        } else {
+            (
+                quote::quote! {},
+                quote::quote! {},
+                count_expired.clone(),
+                quote::quote! {},
+            )
+        };
+        let read_memo = read_with(&get_fn, on_fresh, on_expired);
+        let returned = if with_status {
+            quote::quote! { (ATTR_MEMOIZE_RETURN__, ATTR_MEMOIZE_STATUS__) }
+        } else {
+            quote::quote! { ATTR_MEMOIZE_RETURN__ }
+        };
+
+        if options.coalesce {
+            // The first caller missing a key becomes the leader and calls the original function,
+            // while later callers wait for its result. If the leader is cancelled or panics, its
+            // guard removes the computation in flight, and the remaining callers start over.
+            quote::quote! {
+                #init_status
+                loop {
+                    // The owned key is only needed to keep track of the computation in flight.
+                    let (ATTR_MEMOIZE_KEY__, ATTR_MEMOIZE_JOINED__) = {
+                        let mut ATTR_MEMOIZE_HM__ = #store_ref.lock().unwrap();
+                        #select_cache
+                        if let Some(ATTR_MEMOIZE_RETURN__) = #read_memo {
+                            #count_hit
+                            return #returned
+                        }
+                        let ATTR_MEMOIZE_KEY__ = #syntax_names_tuple_cloned;
+                        let ATTR_MEMOIZE_JOINED__ = ATTR_MEMOIZE_HM__.join(&ATTR_MEMOIZE_KEY__);
+                        (ATTR_MEMOIZE_KEY__, ATTR_MEMOIZE_JOINED__)
+                    };
+                    // Callers waiting for the leader count as hits.
+                    let ATTR_MEMOIZE_SLOT__ = match ATTR_MEMOIZE_JOINED__ {
+                        ::memoize::coalesce::Joined::Leader(ATTR_MEMOIZE_SLOT__) => ATTR_MEMOIZE_SLOT__,
+                        ::memoize::coalesce::Joined::Follower(ATTR_MEMOIZE_WAITER__) => {
+                            match ATTR_MEMOIZE_WAITER__.await {
+                                Some(ATTR_MEMOIZE_RETURN__) => {
+                                    #count_hit
+                                    #on_joined
+                                    return #returned
+                                }
+                                None => continue,
+                            }
+                        }
+                    };
+                    let ATTR_MEMOIZE_GUARD__ = ::memoize::coalesce::LeaderGuard::new(
+                        ATTR_MEMOIZE_SLOT__.clone(),
+                        || {
+                            let mut ATTR_MEMOIZE_HM__ = #store_ref.lock().unwrap();
+                            #select_cache
+                            ATTR_MEMOIZE_HM__.finish(&ATTR_MEMOIZE_KEY__, &ATTR_MEMOIZE_SLOT__);
+                        },
+                    );
+
+                    #count_miss
+                    let ATTR_MEMOIZE_RETURN__ = #call_original;
+
+                    {
+                        let mut ATTR_MEMOIZE_HM__ = #store_ref.lock().unwrap();
+                        #select_cache
+                        #memoize
+                        ATTR_MEMOIZE_HM__.finish(&ATTR_MEMOIZE_KEY__, &ATTR_MEMOIZE_SLOT__);
+                    }
+                    ATTR_MEMOIZE_GUARD__.complete(::std::clone::Clone::clone(&ATTR_MEMOIZE_RETURN__));
+
+                    return #returned;
                 }
             }
-            let ATTR_MEMOIZE_RETURN__ = #memoized_id #forwarding_tuple;
+        } else if locked {
+            quote::quote! {
+                #init_status
+                {
+                    let mut ATTR_MEMOIZE_HM__ = #store_ref.lock().unwrap();
+                    #select_cache
+                    if let Some(ATTR_MEMOIZE_RETURN__) = #read_memo {
+                        #count_hit
+                        return #returned
+                    }
+                }
+                #count_miss
+                let ATTR_MEMOIZE_RETURN__ = #call_original;
 
-            let mut ATTR_MEMOIZE_HM__ = #store_ident.lock().unwrap();
-            #memoize
+                let mut ATTR_MEMOIZE_HM__ = #store_ref.lock().unwrap();
+                #select_cache
+                #memoize
 
-            ATTR_MEMOIZE_RETURN__
+                #returned
+            }
+        } else {
+            quote::quote! {
+                #init_status
+                let ATTR_MEMOIZE_RETURN__ = #store_ref.with(|ATTR_MEMOIZE_HM__| {
+                    let mut ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.borrow_mut();
+                    #select_cache
+                    #read_memo
+                });
+                if let Some(ATTR_MEMOIZE_RETURN__) = ATTR_MEMOIZE_RETURN__ {
+                    #count_hit
+                    return #returned;
+                }
+                #count_miss
+
+                let ATTR_MEMOIZE_RETURN__ = #call_original;
+
+                #store_ref.with(|ATTR_MEMOIZE_HM__| {
+                    let mut ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.borrow_mut();
+                    #select_cache
+                    #memoize
+                });
+
+                #returned
+            }
+        }
+    };
+    let memoizer = make_memoizer(false);
+
+    let vis = &func.vis;
+    let helper_vis = options.helper_visibility.as_ref().unwrap_or(vis);
+
+    let flush_doc = format!("Clears the cache of {}.", fn_link);
+    let size_doc = format!("Returns the number of entries in the cache of {}.", fn_link);
+    let flusher = if let Some(field) = &options.per_instance {
+        quote::quote! {
+            #[doc = #flush_doc]
+            #helper_vis fn #flush_name(&self) {
+                self.#field.lock().unwrap().remove(#fn_name);
+                #counters_ref.flush();
+            }
+        }
+    } else if options.shared_cache {
+        quote::quote! {
+            #[doc = #flush_doc]
+            #helper_vis fn #flush_name() {
+                #store_ref.lock().unwrap().clear();
+                #counters_ref.flush();
+            }
         }
     } else {
         quote::quote! {
-            let ATTR_MEMOIZE_RETURN__ = #store_ident.with(|ATTR_MEMOIZE_HM__| {
-                let mut ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.borrow_mut();
-                #read_memo
-            });
-            if let Some(ATTR_MEMOIZE_RETURN__) = ATTR_MEMOIZE_RETURN__ {
-                return ATTR_MEMOIZE_RETURN__;
+            #[doc = #flush_doc]
+            #helper_vis fn #flush_name() {
+                #store_ref.with(|ATTR_MEMOIZE_HM__| ATTR_MEMOIZE_HM__.borrow_mut().clear());
+                #counters_ref.flush();
             }
+        }
+    };
 
-            let ATTR_MEMOIZE_RETURN__ = #memoized_id #forwarding_tuple;
-
-            #store_ident.with(|ATTR_MEMOIZE_HM__| {
-                let mut ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.borrow_mut();
-                #memoize
-            });
+    // Evaluates `body` with the cache of the function accessible as `ATTR_MEMOIZE_HM__`.
+    let with_cache = |body: proc_macro2::TokenStream| {
+        if locked {
+            quote::quote! {
+                let mut ATTR_MEMOIZE_HM__ = #store_ref.lock().unwrap();
+                #select_cache
+                let ATTR_MEMOIZE_RESULT__ = #body;
+                ATTR_MEMOIZE_RESULT__
+            }
+        } else {
+            quote::quote! {
+                #store_ref.with(|ATTR_MEMOIZE_HM__| {
+                    let mut ATTR_MEMOIZE_HM__ = ATTR_MEMOIZE_HM__.borrow_mut();
+                    #select_cache
+                    let ATTR_MEMOIZE_RESULT__ = #body;
+                    ATTR_MEMOIZE_RESULT__
+                })
+            }
+        }
+    };
 
-            ATTR_MEMOIZE_RETURN__
+    // Helper functions operating on a single entry take the memoized arguments, and therefore have
+    // the generics of the memoized function. They take the receiver if it is part of the key, or
+    // holds the cache.
+    let (impl_generics, _, where_clause) = sig.generics.split_for_impl();
+    let helper_receiver = match sig.receiver() {
+        Some(syn::FnArg::Receiver(recv))
+            if options.per_instance.is_some() || input_params[0].is_memoized =>
+        {
+            if recv.reference.is_some() {
+                quote::quote! { &self, }
+            } else {
+                quote::quote! { self, }
+            }
+        }
+        _ => quote::quote! {},
+    };
+    let helper_params: Vec<_> = input_params
+        .iter()
+        .filter(|p| p.is_memoized && p.arg_name != "self")
+        .map(|p| {
+            let (arg_name, arg_type) = (&p.arg_name, &p.arg_type);
+            quote::quote! { #arg_name: #arg_type }
+        })
+        .collect();
+    // With `Key`, they take the key instead.
+    let (helper_params, helper_prelude) = match &options.key {
+        Some((_, key_type)) => {
+            let digest = digest(quote::quote! { &ATTR_MEMOIZE_CUSTOM_KEY__ });
+            (
+                vec![quote::quote! { key: #key_type }],
+                quote::quote! {
+                    let ATTR_MEMOIZE_CUSTOM_KEY__ = key;
+                    #digest
+                },
+            )
         }
+        None => (helper_params, key_prelude.clone()),
     };
 
-    let vis = &func.vis;
+    let remove_fn = store::cache_remove_method(&options);
+    let removed = match &options.time_to_live {
+        None => quote::quote! { ATTR_MEMOIZE_HM__.#remove_fn(#lookup_key) },
+        Some(_) => quote::quote! {
+            ATTR_MEMOIZE_HM__.#remove_fn(#lookup_key).and_then(|(last_updated, entry_ttl, ATTR_MEMOIZE_RETURN__)|
+                (#is_fresh).then(|| ATTR_MEMOIZE_RETURN__)
+            )
+        },
+    };
+    let invalidate_body = with_cache(removed);
+    let invalidate_doc = format!(
+        "Removes the entry for the given arguments from the cache of {}, and returns its value (if it hasn't expired).",
+        fn_link
+    );
+    let invalidator = quote::quote! {
+        #[doc = #invalidate_doc]
+        #[allow(unused_mut, non_snake_case)]
+        #helper_vis fn #invalidate_name #impl_generics (#helper_receiver #(#helper_params),*)
+            -> Option<#return_type> #where_clause
+        {
+            #helper_prelude
+            #invalidate_body
+        }
+    };
 
-    let flusher = if options.shared_cache {
+    let peek_body = with_cache(read_with(
+        &store::cache_peek_method(&options),
+        quote::quote! {},
+        quote::quote! {},
+    ));
+    let peek_doc = format!(
+        "Returns the cached value of {} for the given arguments, if any, without calling the original function.",
+        fn_link
+    );
+    let peeker = quote::quote! {
+        #[doc = #peek_doc]
+        #[allow(unused_mut, non_snake_case)]
+        #helper_vis fn #peek_name #impl_generics (#helper_receiver #(#helper_params),*)
+            -> Option<#return_type> #where_clause
+        {
+            #helper_prelude
+            #peek_body
+        }
+    };
+    // For LRU caches, peeking doesn't count as a use of the entry, while `memoized_get_*()` does.
+    let getter = if options.lru_max_entries.is_some() {
+        let get_name = syn::Ident::new(&format!("memoized_get_{}", fn_name), sig.span());
+        let body = with_cache(read_with(&get_fn, quote::quote! {}, quote::quote! {}));
+        let doc = format!(
+            "Returns the cached value of {} for the given arguments, if any, without calling the original function, and marks it as most recently used.",
+            fn_link
+        );
         quote::quote! {
-            #vis fn #flush_name() {
-                #store_ident.lock().unwrap().clear();
+            #[doc = #doc]
+            #[allow(unused_mut, non_snake_case)]
+            #helper_vis fn #get_name #impl_generics (#helper_receiver #(#helper_params),*)
+                -> Option<#return_type> #where_clause
+            {
+                #helper_prelude
+                #body
             }
         }
     } else {
+        quote::quote! {}
+    };
+
+    let (flush_path, size_path, stats_path, handle_path) = if in_impl {
+        (
+            quote::quote! { Self::#flush_name },
+            quote::quote! { Self::#size_name },
+            quote::quote! { Self::#stats_name },
+            quote::quote! { Self::#handle_name },
+        )
+    } else {
+        (
+            quote::quote! { #flush_name },
+            quote::quote! { #size_name },
+            quote::quote! { #stats_name },
+            quote::quote! { #handle_name },
+        )
+    };
+
+    // The cache handle is added to the global registry on the first call, or when it is first primed.
+    let register = if options.per_instance.is_some() {
+        quote::quote! {}
+    } else {
+        quote::quote! { #counters_ref.register(#handle_path); }
+    };
+
+    // Names of the value parameters of `memoized_prime_*()` mustn't clash with the arguments.
+    let param_name = |name: &str| {
+        if input_params.iter().any(|p| p.arg_name == name) {
+            syn::Ident::new(&format!("memoized_{}", name), sig.span())
+        } else {
+            syn::Ident::new(name, sig.span())
+        }
+    };
+    let value_param = param_name("value");
+    let ttl_param = param_name("time_to_live");
+    let prime_entry = new_entry(quote::quote! { #value_param }, quote::quote! { None });
+    let prime_body = with_cache(quote::quote! {
+        {
+            #count_insert
+            ATTR_MEMOIZE_HM__.#insert_fn(#syntax_names_tuple, #prime_entry);
+        }
+    });
+    let prime_doc = format!(
+        "Inserts `{}` into the cache of {} for the given arguments, without calling the original function.",
+        value_param, fn_link
+    );
+    let primer = quote::quote! {
+        #[doc = #prime_doc]
+        #[allow(unused_mut, non_snake_case)]
+        #helper_vis fn #prime_name #impl_generics (
+            #helper_receiver #(#helper_params,)* #value_param: #return_type
+        ) #where_clause {
+            #register
+            #helper_prelude
+            #prime_body
+        }
+    };
+    let primer_with_ttl = if options.time_to_live.is_some() {
+        let prime_with_ttl_name = syn::Ident::new(&format!("{}_with_ttl", prime_name), sig.span());
+        let entry = new_entry(
+            quote::quote! { #value_param },
+            quote::quote! { Some(#ttl_param) },
+        );
+        let body = with_cache(quote::quote! {
+            {
+                #count_insert
+                ATTR_MEMOIZE_HM__.#insert_fn(#syntax_names_tuple, #entry);
+            }
+        });
+        let doc = format!(
+            "Inserts `{}` into the cache of {} for the given arguments, expiring after `{}` instead of the `TimeToLive`.",
+            value_param, fn_link, ttl_param
+        );
         quote::quote! {
-            #vis fn #flush_name() {
-                #store_ident.with(|ATTR_MEMOIZE_HM__| ATTR_MEMOIZE_HM__.borrow_mut().clear());
+            #[doc = #doc]
+            #[allow(unused_mut, non_snake_case)]
+            #helper_vis fn #prime_with_ttl_name #impl_generics (
+                #helper_receiver #(#helper_params,)* #value_param: #return_type,
+                #ttl_param: std::time::Duration
+            ) #where_clause {
+                #register
+                #helper_prelude
+                #body
             }
         }
+    } else {
+        quote::quote! {}
     };
 
-    let size_func = if options.shared_cache {
+    let size_func = if let Some(field) = &options.per_instance {
+        quote::quote! {
+            #[doc = #size_doc]
+            #helper_vis fn #size_name(&self) -> usize {
+                self.#field.lock().unwrap().get(#fn_name).map_or(0, |c| c.len())
+            }
+        }
+    } else if options.shared_cache {
         quote::quote! {
-            #vis fn #size_name() -> usize {
-                #store_ident.lock().unwrap().len()
+            #[doc = #size_doc]
+            #helper_vis fn #size_name() -> usize {
+                #store_ref.lock().unwrap().len()
             }
         }
     } else {
         quote::quote! {
-            #vis fn #size_name() -> usize {
-                #store_ident.with(|ATTR_MEMOIZE_HM__| ATTR_MEMOIZE_HM__.borrow().len())
+            #[doc = #size_doc]
+            #helper_vis fn #size_name() -> usize {
+                #store_ref.with(|ATTR_MEMOIZE_HM__| ATTR_MEMOIZE_HM__.borrow().len())
             }
         }
     };
 
+    let stats_doc = format!(
+        "Returns the statistics of the cache of {}, counted across all threads{}.",
+        fn_link,
+        if options.per_instance.is_some() {
+            " and instances"
+        } else {
+            ""
+        }
+    );
+    let stats_func = quote::quote! {
+        #[doc = #stats_doc]
+        #helper_vis fn #stats_name() -> ::memoize::CacheStats {
+            #counters_ref.snapshot()
+        }
+    };
+
+    // The cache handle refers to the helper functions. It is an associated constant inside an
+    // `impl` block, where there are no statics. Per-instance caches have no handle.
+    let capacity = match options.lru_max_entries {
+        Some(cap) => quote::quote! { Some(#cap) },
+        None => quote::quote! { None },
+    };
+    let storage = if options.shared_cache {
+        quote::quote! { ::memoize::Storage::Shared }
+    } else {
+        quote::quote! { ::memoize::Storage::ThreadLocal }
+    };
+    let time_to_live = match &options.time_to_live {
+        Some(ttl) => quote::quote! { Some(|| #ttl) },
+        None => quote::quote! { None },
+    };
+    let handle_doc = format!("Handle to the cache of {}.", fn_link);
+    let handle_init = quote::quote! {
+        ::memoize::CacheHandle::new(
+            #fn_name,
+            module_path!(),
+            #storage,
+            #capacity,
+            #time_to_live,
+            #flush_path,
+            #size_path,
+            #stats_path,
+        )
+    };
+    let handle = if options.per_instance.is_some() {
+        quote::quote! {}
+    } else if in_impl {
+        quote::quote! {
+            #(#cfg_attrs)*
+            #[doc = #handle_doc]
+            #helper_vis const #handle_name: ::memoize::CacheHandle = #handle_init;
+        }
+    } else {
+        quote::quote! {
+            #(#cfg_attrs)*
+            #[doc = #handle_doc]
+            #helper_vis static #handle_name: ::memoize::CacheHandle = #handle_init;
+        }
+    };
+
+    // Helper functions are removed by the same `cfg`s, and subject to the same lint levels, as the
+    // memoized function.
+    let helpers: Vec<_> = [
+        flusher,
+        size_func,
+        stats_func,
+        invalidator,
+        primer,
+        primer_with_ttl,
+        peeker,
+        getter,
+    ]
+    .iter()
+    .filter(|helper| !helper.is_empty())
+    .map(|helper| quote::quote! { #(#cfg_attrs)* #(#lint_attrs)* #helper })
+    .collect();
+
+    // Per-instance caches have no store item.
+    let store = if store.is_empty() {
+        store
+    } else {
+        quote::quote! { #(#cfg_attrs)* #store }
+    };
+    let counters = quote::quote! { #(#cfg_attrs)* #counters };
+
+    let (renamed_fn, nested_fn) = if options.no_original && !in_impl {
+        (quote::quote! {}, renamed_fn.to_token_stream())
+    } else {
+        (renamed_fn.to_token_stream(), quote::quote! {})
+    };
+
+    // Variants of the memoized function have its signature (except for the ABI), and are
+    // generated like helper functions.
+    let variant =
+        |name: String, output: syn::ReturnType, doc: String, body: proc_macro2::TokenStream| {
+            let mut variant_sig = wrapper_sig.clone();
+            variant_sig.ident = syn::Ident::new(&name, sig.span());
+            variant_sig.abi = None;
+            variant_sig.output = output;
+            quote::quote! {
+                #(#cfg_attrs)*
+                #(#lint_attrs)*
+                #[doc = #doc]
+                #[allow(unused_variables, unused_mut, non_snake_case)]
+                #helper_vis #variant_sig {
+                    #nested_fn
+                    #register
+                    #key_prelude
+                    #body
+                }
+            }
+        };
+    // `<fn>_with_status` returns the `CacheStatus` of the call, too.
+    let status_fn = variant(
+        format!("{}_with_status", fn_name),
+        syn::parse_quote! { -> (#return_type, ::memoize::CacheStatus) },
+        format!(
+            "Like {}, but also returns whether the value was cached.",
+            fn_link
+        ),
+        make_memoizer(true),
+    );
+    // `memoized_refresh_<fn>` always calls the original function, and replaces the cached value.
+    let refresh_store = with_cache(quote::quote! { { #memoize } });
+    let refresh_fn = variant(
+        format!("memoized_refresh_{}", fn_name),
+        wrapper_sig.output.clone(),
+        format!(
+            "Calls the original function of {}, and replaces the cached value for the given arguments with the result.",
+            fn_link
+        ),
+        quote::quote! {
+            let ATTR_MEMOIZE_RETURN__ = #call_original;
+            { #refresh_store };
+            ATTR_MEMOIZE_RETURN__
+        },
+    );
+
     quote::quote! {
         #renamed_fn
-        #flusher
-        #size_func
+        #(#helpers)*
+        #status_fn
+        #refresh_fn
+
+        #handle
         #store
+        #counters
 
-        #[allow(unused_variables, unused_mut)]
-        #vis #sig {
+        #(#cfg_attrs)*
+        #(#wrapper_attrs)*
+        #[allow(unused_variables, unused_mut, non_snake_case)]
+        #vis #wrapper_sig {
+            #nested_fn
+            #register
+            #key_prelude
             #memoizer
         }
     }
-    .into()
 }
 
 /// An argument of the memoized function.
@@ -464,45 +1747,228 @@ struct FnArgument {
     /// Type of the argument.
     arg_type: Box<syn::Type>,
 
+    /// Type of the argument in the key. This is the owned type for borrowed arguments.
+    key_type: Box<syn::Type>,
+
+    /// Type a reference to which is used to look up the argument: the key type for normalized
+    /// arguments, the referenced type for borrowed arguments, and the argument type otherwise.
+    ref_type: Box<syn::Type>,
+
     /// Identifier (name) of the argument.
     arg_name: syn::Ident,
 
     /// Whether or not this specific argument is included in the memoization.
     is_memoized: bool,
+
+    /// Whether this argument is a shared reference (including `&self`), which is converted into an
+    /// owned key using `ToOwned`.
+    is_borrowed: bool,
+
+    /// Function given by `KeyWith`, which computes the key from a reference to the argument.
+    normalizer: Option<Path>,
+}
+
+impl FnArgument {
+    /// Identifier of the key of the argument: the argument itself, or the local its normalized key
+    /// is bound to.
+    fn key_ident(&self) -> syn::Ident {
+        if self.normalizer.is_some() {
+            syn::Ident::new(
+                &format!("ATTR_MEMOIZE_KEY_{}__", self.arg_name),
+                self.arg_name.span(),
+            )
+        } else {
+            self.arg_name.clone()
+        }
+    }
+
+    /// Statement binding the key of a normalized argument, without consuming it.
+    fn normalized(&self) -> Option<proc_macro2::TokenStream> {
+        let ident = &self.arg_name;
+        let key_ident = self.key_ident();
+        self.normalizer.as_ref().map(|normalizer| {
+            if self.is_borrowed {
+                quote::quote! { let #key_ident = #normalizer(#ident); }
+            } else {
+                quote::quote! { let #key_ident = #normalizer(&#ident); }
+            }
+        })
+    }
 }
 
 fn check_signature(
     sig: &syn::Signature,
     options: &CacheOptions,
 ) -> Result<Vec<FnArgument>, syn::Error> {
-    if sig.inputs.is_empty() {
-        return Ok(vec![]);
+    if options.per_instance.is_some() && sig.receiver().is_none() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "PerInstance can only be used on methods!",
+        ));
+    }
+    if let (Some(_), Some((arg, _))) = (&options.key, options.key_with.first()) {
+        return Err(syn::Error::new(
+            arg.span(),
+            "Key and KeyWith can't be used together!",
+        ));
     }
+    let normalizer = |arg_name: &syn::Ident| {
+        options
+            .key_with
+            .iter()
+            .find(|(arg, _)| arg == arg_name)
+            .map(|(_, normalizer)| normalizer.clone())
+    };
 
     let mut params = vec![];
 
-    for a in &sig.inputs {
-        if let syn::FnArg::Typed(ref arg) = a {
-            let arg_type = arg.ty.clone();
+    for (i, a) in sig.inputs.iter().enumerate() {
+        match a {
+            syn::FnArg::Receiver(recv) => {
+                let arg_name = syn::Ident::from(recv.self_token);
+                if options.per_instance.is_some() && recv.reference.is_none() {
+                    return Err(syn::Error::new(
+                        recv.span(),
+                        "PerInstance requires a `&self` or `&mut self` receiver!",
+                    ));
+                }
+                // A per-instance cache is only used with one receiver.
+                let is_memoized = options.per_instance.is_none()
+                    && options.key.is_none()
+                    && !options.ignore.contains(&arg_name);
+                if is_memoized && recv.reference.is_some() && recv.mutability.is_some() {
+                    return Err(syn::Error::new(
+                        recv.span(),
+                        "Cannot memoize methods taking `&mut self`, unless `self` is ignored!",
+                    ));
+                }
+                let self_type: Box<syn::Type> = Box::new(syn::parse_quote! { Self });
+                params.push(FnArgument {
+                    arg_type: self_type.clone(),
+                    key_type: self_type.clone(),
+                    ref_type: self_type,
+                    normalizer: normalizer(&arg_name),
+                    arg_name,
+                    is_memoized,
+                    is_borrowed: recv.reference.is_some(),
+                });
+            }
+            syn::FnArg::Typed(arg) => {
+                let arg_type = arg.ty.clone();
 
-            if let syn::Pat::Ident(patident) = &*arg.pat {
-                let arg_name = patident.ident.clone();
-                let is_memoized = !options.ignore.contains(&arg_name);
+                let arg_name = match &*arg.pat {
+                    syn::Pat::Ident(patident) => {
+                        if patident.ident == "self" {
+                            return Err(syn::Error::new(
+                                arg.span(),
+                                "Cannot memoize methods with an explicitly typed `self`!",
+                            ));
+                        }
+                        patident.ident.clone()
+                    }
+                    // Destructured arguments are passed to the memoized function under a hidden
+                    // name, and destructured by the original function.
+                    syn::Pat::Tuple(_) | syn::Pat::TupleStruct(_) | syn::Pat::Struct(_) => {
+                        syn::Ident::new(&format!("ATTR_MEMOIZE_ARG_{}__", i), arg.pat.span())
+                    }
+                    _ => {
+                        return Err(syn::Error::new(
+                            arg.pat.span(),
+                            "Cannot memoize arbitrary patterns!",
+                        ))
+                    }
+                };
+                let is_memoized = options.key.is_none() && !options.ignore.contains(&arg_name);
+                // Shared references are stored as the corresponding owned type, except for
+                // `&'static T` (unless `T` is unsized), which is stored as it is, so that `T` doesn't
+                // need to be `Clone`.
+                let (key_type, ref_type, is_borrowed) = match &*arg_type {
+                    syn::Type::Reference(r)
+                        if r.mutability.is_none()
+                            && (has_owned_form(&r.elem)
+                                || r.lifetime.as_ref().is_none_or(|l| l.ident != "static")) =>
+                    {
+                        let elem = &r.elem;
+                        (
+                            Box::new(
+                                syn::parse_quote! { <#elem as ::std::borrow::ToOwned>::Owned },
+                            ),
+                            elem.clone(),
+                            true,
+                        )
+                    }
+                    _ => (arg_type.clone(), arg_type.clone(), false),
+                };
+                // Floating-point arguments are stored as a `FloatKey` of their bit pattern, unless
+                // they are normalized explicitly, into a `FloatKey` as well.
+                let mut normalizer = normalizer(&arg_name);
+                let key_type = if is_float(&ref_type) {
+                    normalizer = normalizer.or_else(|| {
+                        Some(if options.bitwise_floats {
+                            syn::parse_quote! { ::memoize::keys::FloatKey::bitwise }
+                        } else {
+                            syn::parse_quote! { ::memoize::keys::FloatKey::canonical }
+                        })
+                    });
+                    Box::new(syn::parse_quote! { ::memoize::keys::FloatKey })
+                } else {
+                    key_type
+                };
+                // Normalized arguments are looked up by their computed key.
+                let ref_type = if normalizer.is_some() {
+                    key_type.clone()
+                } else {
+                    ref_type
+                };
                 params.push(FnArgument {
                     arg_type,
+                    key_type,
+                    ref_type,
                     arg_name,
                     is_memoized,
+                    is_borrowed,
+                    normalizer,
                 });
-            } else {
-                return Err(syn::Error::new(
-                    sig.span(),
-                    "Cannot memoize arbitrary patterns!",
-                ));
             }
         }
     }
+    for (arg, _) in &options.key_with {
+        if !params.iter().any(|p| p.is_memoized && p.arg_name == *arg) {
+            return Err(syn::Error::new(
+                arg.span(),
+                "KeyWith must name a memoized argument!",
+            ));
+        }
+    }
     Ok(params)
 }
 
+/// Whether `ty` is `f32` or `f64`.
+fn is_float(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => {
+            path.path.is_ident("f32") || path.path.is_ident("f64")
+        }
+        _ => false,
+    }
+}
+
+/// Whether `ty` is one of the (unsized) types which are only passed by reference, and stored in
+/// their owned form, or a floating-point type.
+fn has_owned_form(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Slice(_) => true,
+        syn::Type::Path(path) if path.qself.is_none() => {
+            path.path.segments.last().is_some_and(|s| {
+                s.arguments.is_empty()
+                    && ["str", "Path", "OsStr", "CStr", "f32", "f64"]
+                        .iter()
+                        .any(|n| s.ident == n)
+            })
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {}
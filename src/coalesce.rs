@@ -0,0 +1,155 @@
+//! Deduplication of concurrent calls to `async fn`s memoized with `Coalesce`, used by the code
+//! generated by `#[memoize]`.
+//!
+//! The store of such a function is a [`Coalesced`] cache, which keeps a slot for every computation
+//! in flight next to the completed values. The first caller missing a key becomes the leader and
+//! computes the value; later callers wait on the leader's slot. If the leader is cancelled or
+//! panics, its slot is removed and the waiting callers retry.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+enum SlotState<V> {
+    Pending(Vec<Waker>),
+    /// The computation has finished; `None` if the leader was cancelled or panicked.
+    Done(Option<V>),
+}
+
+/// A computation in flight.
+pub struct Slot<V> {
+    state: Mutex<SlotState<V>>,
+}
+
+impl<V> Slot<V> {
+    fn new() -> Slot<V> {
+        Slot {
+            state: Mutex::new(SlotState::Pending(vec![])),
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), SlotState::Pending(_))
+    }
+
+    fn finish(&self, value: Option<V>) {
+        let state = std::mem::replace(&mut *self.state.lock().unwrap(), SlotState::Done(value));
+        if let SlotState::Pending(wakers) = state {
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+/// A cache `C` together with the computations in flight for keys of type `K`.
+///
+/// Dereferences to the underlying cache.
+pub struct Coalesced<C, K, V> {
+    cache: C,
+    pending: HashMap<K, Arc<Slot<V>>>,
+}
+
+/// Outcome of [`Coalesced::join()`].
+pub enum Joined<V> {
+    /// No computation is in flight: the caller has to compute the value, and complete the slot.
+    Leader(Arc<Slot<V>>),
+    /// Another caller is computing the value.
+    Follower(Waiter<V>),
+}
+
+impl<C, K: Hash + Eq + Clone, V> Coalesced<C, K, V> {
+    pub fn new(cache: C) -> Coalesced<C, K, V> {
+        Coalesced {
+            cache,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Joins the computation in flight for `key`, or starts a new one.
+    pub fn join(&mut self, key: &K) -> Joined<V> {
+        if let Some(slot) = self.pending.get(key) {
+            if slot.is_pending() {
+                return Joined::Follower(Waiter { slot: slot.clone() });
+            }
+        }
+        let slot = Arc::new(Slot::new());
+        self.pending.insert(key.clone(), slot.clone());
+        Joined::Leader(slot)
+    }
+
+    /// Removes `slot` from the computations in flight, if it is still registered for `key`.
+    pub fn finish(&mut self, key: &K, slot: &Arc<Slot<V>>) {
+        if matches!(self.pending.get(key), Some(s) if Arc::ptr_eq(s, slot)) {
+            self.pending.remove(key);
+        }
+    }
+}
+
+impl<C, K, V> Deref for Coalesced<C, K, V> {
+    type Target = C;
+    fn deref(&self) -> &C {
+        &self.cache
+    }
+}
+
+impl<C, K, V> DerefMut for Coalesced<C, K, V> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+}
+
+/// Future resolving to the value computed by the leader, or `None` if the leader gave up.
+pub struct Waiter<V> {
+    slot: Arc<Slot<V>>,
+}
+
+impl<V: Clone> Future for Waiter<V> {
+    type Output = Option<V>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<V>> {
+        match &mut *self.slot.state.lock().unwrap() {
+            SlotState::Done(value) => Poll::Ready(value.clone()),
+            SlotState::Pending(wakers) => {
+                if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Held by the leader while computing. If dropped before [`LeaderGuard::complete()`] is called,
+/// i.e. when the leader is cancelled or panics, it runs `abandon` (which removes the slot from
+/// the store) and wakes up all waiters so that they retry.
+pub struct LeaderGuard<V, F: FnMut()> {
+    slot: Arc<Slot<V>>,
+    abandon: Option<F>,
+}
+
+impl<V, F: FnMut()> LeaderGuard<V, F> {
+    pub fn new(slot: Arc<Slot<V>>, abandon: F) -> LeaderGuard<V, F> {
+        LeaderGuard {
+            slot,
+            abandon: Some(abandon),
+        }
+    }
+
+    /// Hands `value` to all waiters. The slot must have been removed from the store already.
+    pub fn complete(mut self, value: V) {
+        self.abandon = None;
+        self.slot.finish(Some(value));
+    }
+}
+
+impl<V, F: FnMut()> Drop for LeaderGuard<V, F> {
+    fn drop(&mut self) {
+        if let Some(mut abandon) = self.abandon.take() {
+            abandon();
+            self.slot.finish(None);
+        }
+    }
+}
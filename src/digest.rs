@@ -0,0 +1,52 @@
+//! Digests of cache keys, used by the code generated by `#[memoize]` for functions with
+//! `HashKeys`.
+//!
+//! Instead of the memoized arguments, only a 128-bit digest of them is stored in the cache. With
+//! `HashKeys: Verify`, a 64-bit fingerprint computed by a different hash function is stored next
+//! to it, so that a collision of digests doesn't return the value of different arguments.
+//! This module is not part of the public API.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Seed distinguishing the second half of a digest from the first one.
+const SECOND_HALF_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Returns the 128-bit digest of `key`.
+pub fn digest<K: Hash + ?Sized>(key: &K) -> u128 {
+    let mut first = DefaultHasher::new();
+    key.hash(&mut first);
+    let mut second = DefaultHasher::new();
+    SECOND_HALF_SEED.hash(&mut second);
+    key.hash(&mut second);
+    (u128::from(first.finish()) << 64) | u128::from(second.finish())
+}
+
+/// Returns the 128-bit digest of `key` together with its fingerprint.
+pub fn verified_digest<K: Hash + ?Sized>(key: &K) -> (u128, u64) {
+    let mut fingerprint = Fnv1a::default();
+    key.hash(&mut fingerprint);
+    (digest(key), fingerprint.finish())
+}
+
+/// The 64-bit FNV-1a hash function.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,99 @@
+//! Type-erased cache storage, used by the code generated by `#[memoize]`.
+//!
+//! A `static` inside an `impl` block (or a generic function) cannot mention `Self` or any generic
+//! parameters, so the caches of such functions are stored behind `dyn Any`. They are looked up by
+//! the `TypeId` of the function item of the instantiation the cache belongs to. This module is not
+//! part of the public API.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A cache whose concrete type has been erased.
+pub trait ErasedCache: Any {
+    /// Number of entries in the cache.
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Wraps a concrete cache, remembering how to count its entries.
+pub struct Erased<C> {
+    cache: C,
+    len: fn(&C) -> usize,
+}
+
+impl<C> Erased<C> {
+    pub fn new(cache: C, len: fn(&C) -> usize) -> Erased<C> {
+        Erased { cache, len }
+    }
+}
+
+impl<C: 'static> ErasedCache for Erased<C> {
+    fn len(&self) -> usize {
+        (self.len)(&self.cache)
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Returns the `TypeId` of a value's type, e.g. of a function item (which is unique for every
+/// instantiation of a generic function).
+pub fn type_id_of<T: 'static>(_: &T) -> TypeId {
+    TypeId::of::<T>()
+}
+
+/// A set of caches, indexed by `TypeId`.
+///
+/// `E` is either `dyn ErasedCache` (thread-local caches) or `dyn ErasedCache + Send` (shared
+/// caches).
+pub struct TypeMap<E: ?Sized> {
+    caches: HashMap<TypeId, Box<E>>,
+}
+
+impl<E: ?Sized + ErasedCache> TypeMap<E> {
+    pub fn new() -> TypeMap<E> {
+        TypeMap {
+            caches: HashMap::new(),
+        }
+    }
+
+    /// Returns the cache of type `C` stored for `id`, creating it using `init` if it doesn't exist
+    /// yet. `init` must return an `Erased<C>`.
+    pub fn get_or_insert_with<C: 'static>(
+        &mut self,
+        id: TypeId,
+        init: impl FnOnce() -> Box<E>,
+    ) -> &mut C {
+        let erased = self
+            .caches
+            .entry(id)
+            .or_insert_with(init)
+            .as_any_mut()
+            .downcast_mut::<Erased<C>>()
+            .expect("memoize: type-erased cache has unexpected type");
+        &mut erased.cache
+    }
+
+    /// Total number of entries in all caches.
+    pub fn len(&self) -> usize {
+        self.caches.values().map(|c| c.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all caches.
+    pub fn clear(&mut self) {
+        self.caches.clear();
+    }
+}
+
+impl<E: ?Sized + ErasedCache> Default for TypeMap<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,145 @@
+use crate::stats::CacheStats;
+
+use std::fmt;
+use std::time::Duration;
+
+/// Operations on the cache of a memoized function, for code handling caches generically.
+pub trait CacheControl {
+    /// Name of the memoized function.
+    fn name(&self) -> &'static str;
+
+    /// Clears the cache.
+    fn flush(&self);
+
+    /// Number of cached entries.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of cached entries, if limited with `Capacity`.
+    fn capacity(&self) -> Option<usize>;
+
+    /// Hit, miss and eviction statistics.
+    fn stats(&self) -> CacheStats;
+}
+
+/// Handle to the cache of a memoized function `f`, generated as `F_CACHE` (a static, or an
+/// associated constant inside an `impl` block) with the same visibility as the helper functions.
+///
+/// For thread-local caches, the handle operates on the cache of the current thread. No handle is
+/// generated for methods with `PerInstance` caches.
+///
+/// ```
+/// use memoize::{memoize, CacheControl};
+///
+/// #[memoize]
+/// fn square(n: u64) -> u64 {
+///     n * n
+/// }
+///
+/// square(2);
+/// let caches: &[&dyn CacheControl] = &[&SQUARE_CACHE];
+/// for cache in caches {
+///     assert_eq!(cache.name(), "square");
+///     assert_eq!(cache.len(), 1);
+///     cache.flush();
+/// }
+/// assert!(SQUARE_CACHE.is_empty());
+/// ```
+#[derive(Clone, Copy)]
+pub struct CacheHandle {
+    name: &'static str,
+    module_path: &'static str,
+    storage: Storage,
+    capacity: Option<usize>,
+    time_to_live: Option<fn() -> Duration>,
+    flush: fn(),
+    len: fn() -> usize,
+    stats: fn() -> CacheStats,
+}
+
+/// Where the cache of a memoized function is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Storage {
+    /// Every thread has its own cache (the default).
+    ThreadLocal,
+    /// All threads share a cache (`SharedCache`).
+    Shared,
+}
+
+impl CacheHandle {
+    #[doc(hidden)]
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(
+        name: &'static str,
+        module_path: &'static str,
+        storage: Storage,
+        capacity: Option<usize>,
+        time_to_live: Option<fn() -> Duration>,
+        flush: fn(),
+        len: fn() -> usize,
+        stats: fn() -> CacheStats,
+    ) -> CacheHandle {
+        CacheHandle {
+            name,
+            module_path,
+            storage,
+            capacity,
+            time_to_live,
+            flush,
+            len,
+            stats,
+        }
+    }
+
+    /// Path of the module containing the memoized function.
+    pub fn module_path(&self) -> &'static str {
+        self.module_path
+    }
+
+    pub fn storage(&self) -> Storage {
+        self.storage
+    }
+
+    /// The `TimeToLive` of cached entries, if any.
+    pub fn time_to_live(&self) -> Option<Duration> {
+        self.time_to_live.map(|ttl| ttl())
+    }
+}
+
+impl CacheControl for CacheHandle {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn flush(&self) {
+        (self.flush)()
+    }
+
+    fn len(&self) -> usize {
+        (self.len)()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    fn stats(&self) -> CacheStats {
+        (self.stats)()
+    }
+}
+
+impl fmt::Debug for CacheHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheHandle")
+            .field("name", &self.name)
+            .field("module_path", &self.module_path)
+            .field("storage", &self.storage)
+            .field("capacity", &self.capacity)
+            .field("time_to_live", &self.time_to_live())
+            .field("len", &self.len())
+            .finish()
+    }
+}
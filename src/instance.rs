@@ -0,0 +1,94 @@
+use crate::erased::{ErasedCache, TypeMap};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{LockResult, Mutex, MutexGuard};
+
+type Caches = HashMap<&'static str, TypeMap<dyn ErasedCache + Send>>;
+
+/// Storage for the caches of methods memoized with `#[memoize(PerInstance)]`.
+///
+/// Add a field of this type to your struct (by default named `memoize_cache`; a different name
+/// can be given as `PerInstance: field_name`). The cached values are then dropped together with
+/// the instance.
+///
+/// Cloning an `InstanceCache` results in an empty cache. It compares equal to any other
+/// `InstanceCache` and doesn't contribute to hashes, so that structs containing one can still
+/// derive these traits.
+///
+/// ```
+/// use memoize::{memoize, InstanceCache};
+///
+/// struct Primes {
+///     limit: u64,
+///     memoize_cache: InstanceCache,
+/// }
+///
+/// impl Primes {
+///     #[memoize(PerInstance)]
+///     fn count(&self, from: u64) -> usize {
+///         (from..self.limit).filter(|n| (2..*n).all(|d| n % d != 0)).count()
+///     }
+/// }
+///
+/// let p = Primes { limit: 100, memoize_cache: InstanceCache::new() };
+/// assert_eq!(p.count(50), 10);
+/// assert_eq!(p.memoized_size_count(), 1);
+/// ```
+#[derive(Default)]
+pub struct InstanceCache {
+    caches: Mutex<Caches>,
+}
+
+impl InstanceCache {
+    pub fn new() -> InstanceCache {
+        InstanceCache::default()
+    }
+
+    /// Clears the caches of all memoized methods of this instance.
+    pub fn clear(&self) {
+        self.caches.lock().unwrap().clear();
+    }
+
+    /// Total number of cached entries, across all memoized methods.
+    pub fn len(&self) -> usize {
+        self.caches.lock().unwrap().values().map(|c| c.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Locks the caches, which are indexed by method name. Used by generated code.
+    #[doc(hidden)]
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, Caches>> {
+        self.caches.lock()
+    }
+}
+
+impl Clone for InstanceCache {
+    fn clone(&self) -> Self {
+        InstanceCache::new()
+    }
+}
+
+impl PartialEq for InstanceCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for InstanceCache {}
+
+impl Hash for InstanceCache {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+impl fmt::Debug for InstanceCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstanceCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
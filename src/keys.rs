@@ -0,0 +1,115 @@
+//! Borrowed views of cache keys, used by the code generated by `#[memoize]`.
+//!
+//! The key of a function with several memoized arguments is a tuple of owned values, e.g.
+//! `(String, usize)` for `fn f(a: &str, b: usize)`. In order to look it up without allocating,
+//! `(String, usize)` can be borrowed as `dyn KeyView2<str, usize>`, which is also implemented by
+//! `(&str, &usize)`. Hashing and comparison of views match those of the owned tuples.
+//!
+//! Floating-point arguments are stored as a [`FloatKey`] of their bit pattern, as `f32` and `f64`
+//! aren't `Eq` and `Hash`.
+//! This module is not part of the public API, except for [`FloatKey`], which is reexported.
+
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+
+macro_rules! key_view {
+    ($name:ident: $($q:ident $k:ident $get:ident $field:tt),+) => {
+        pub trait $name<$($q: ?Sized),+> {
+            $(fn $get(&self) -> &$q;)+
+        }
+
+        impl<$($q: ?Sized,)+ $($k: Borrow<$q>),+> $name<$($q),+> for ($($k,)+) {
+            $(fn $get(&self) -> &$q {
+                self.$field.borrow()
+            })+
+        }
+
+        impl<'a, $($q: ?Sized,)+ $($k: Borrow<$q> + 'a),+> Borrow<dyn $name<$($q),+> + 'a>
+            for ($($k,)+)
+        {
+            fn borrow(&self) -> &(dyn $name<$($q),+> + 'a) {
+                self
+            }
+        }
+
+        impl<$($q: ?Sized + Hash),+> Hash for dyn $name<$($q),+> + '_ {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                $(self.$get().hash(state);)+
+            }
+        }
+
+        impl<$($q: ?Sized + PartialEq),+> PartialEq for dyn $name<$($q),+> + '_ {
+            fn eq(&self, other: &Self) -> bool {
+                $(self.$get() == other.$get())&&+
+            }
+        }
+
+        impl<$($q: ?Sized + Eq),+> Eq for dyn $name<$($q),+> + '_ {}
+    };
+}
+
+key_view!(KeyView2: Q0 K0 k0 0, Q1 K1 k1 1);
+key_view!(KeyView3: Q0 K0 k0 0, Q1 K1 k1 1, Q2 K2 k2 2);
+key_view!(KeyView4: Q0 K0 k0 0, Q1 K1 k1 1, Q2 K2 k2 2, Q3 K3 k3 3);
+key_view!(KeyView5: Q0 K0 k0 0, Q1 K1 k1 1, Q2 K2 k2 2, Q3 K3 k3 3, Q4 K4 k4 4);
+key_view!(KeyView6: Q0 K0 k0 0, Q1 K1 k1 1, Q2 K2 k2 2, Q3 K3 k3 3, Q4 K4 k4 4, Q5 K5 k5 5);
+key_view!(KeyView7: Q0 K0 k0 0, Q1 K1 k1 1, Q2 K2 k2 2, Q3 K3 k3 3, Q4 K4 k4 4, Q5 K5 k5 5, Q6 K6 k6 6);
+key_view!(KeyView8: Q0 K0 k0 0, Q1 K1 k1 1, Q2 K2 k2 2, Q3 K3 k3 3, Q4 K4 k4 4, Q5 K5 k5 5, Q6 K6 k6 6, Q7 K7 k7 7);
+
+/// Key of a floating-point argument, which is compared and hashed by its bit pattern.
+///
+/// `KeyWith` functions of floating-point arguments return a `FloatKey`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FloatKey(u64);
+
+impl FloatKey {
+    /// Key of `f` in which all NaNs are equal, and `-0.0` is equal to `0.0` (`FloatKeys: Canonical`).
+    pub fn canonical<F: KeyFloat>(f: &F) -> FloatKey {
+        FloatKey(f.canonical().bits())
+    }
+
+    /// Key of `f` as it is (`FloatKeys: Bitwise`).
+    pub fn bitwise<F: KeyFloat>(f: &F) -> FloatKey {
+        FloatKey(f.bits())
+    }
+}
+
+/// Floating-point types that can be memoized as [`FloatKey`]s.
+pub trait KeyFloat: Copy {
+    fn bits(self) -> u64;
+
+    /// Replaces all NaNs by a single one, and `-0.0` by `0.0`.
+    fn canonical(self) -> Self;
+}
+
+impl KeyFloat for f32 {
+    fn bits(self) -> u64 {
+        u64::from(self.to_bits())
+    }
+
+    fn canonical(self) -> f32 {
+        if self.is_nan() {
+            f32::NAN
+        } else if self == 0.0 {
+            0.0
+        } else {
+            self
+        }
+    }
+}
+
+impl KeyFloat for f64 {
+    fn bits(self) -> u64 {
+        self.to_bits()
+    }
+
+    fn canonical(self) -> f64 {
+        if self.is_nan() {
+            f64::NAN
+        } else if self == 0.0 {
+            0.0
+        } else {
+            self
+        }
+    }
+}
@@ -3,3 +3,23 @@ pub use ::memoize_inner::memoize;
 
 #[cfg(feature = "full")]
 pub use ::lru;
+
+#[doc(hidden)]
+pub mod coalesce;
+#[doc(hidden)]
+pub mod digest;
+#[doc(hidden)]
+pub mod erased;
+mod handle;
+mod instance;
+#[doc(hidden)]
+pub mod keys;
+mod registry;
+#[doc(hidden)]
+pub mod stats;
+
+pub use handle::{CacheControl, CacheHandle, Storage};
+pub use instance::InstanceCache;
+pub use keys::FloatKey;
+pub use registry::{caches, flush_all};
+pub use stats::{CacheStats, CacheStatus};
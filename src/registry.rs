@@ -0,0 +1,43 @@
+//! Registry of all memoized functions in the process.
+
+use crate::handle::{CacheControl, CacheHandle};
+
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Mutex<Vec<CacheHandle>> = Mutex::new(vec![]);
+}
+
+/// Called by every memoized function (except those with `PerInstance` caches) on its first call
+/// or priming.
+pub(crate) fn register(handle: CacheHandle) {
+    REGISTRY.lock().unwrap().push(handle);
+}
+
+/// Returns handles to the caches of all memoized functions that have been called so far.
+///
+/// ```
+/// use memoize::{memoize, CacheControl};
+///
+/// #[memoize(SharedCache)]
+/// fn square(n: u64) -> u64 {
+///     n * n
+/// }
+///
+/// square(2);
+/// let cache = memoize::caches().into_iter().find(|c| c.name() == "square").unwrap();
+/// assert_eq!(cache.len(), 1);
+/// memoize::flush_all();
+/// assert_eq!(cache.len(), 0);
+/// ```
+pub fn caches() -> Vec<CacheHandle> {
+    REGISTRY.lock().unwrap().clone()
+}
+
+/// Clears the caches of all memoized functions. Thread-local caches are only cleared on the
+/// current thread.
+pub fn flush_all() {
+    for cache in caches() {
+        cache.flush();
+    }
+}
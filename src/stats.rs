@@ -0,0 +1,132 @@
+//! Statistics of memoized functions, counted by the code generated by `#[memoize]`.
+
+use crate::handle::CacheHandle;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+/// Outcome of a call of a memoized function `f`, as returned by `f_with_status()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CacheStatus {
+    /// The value was cached. With `TimeToLive`, `age` is the time since it was computed.
+    Hit { age: Option<Duration> },
+    /// No value was cached, so the original function was called.
+    Miss,
+    /// The cached value had expired, so the original function was called.
+    Expired,
+}
+
+impl CacheStatus {
+    pub fn is_hit(&self) -> bool {
+        matches!(self, CacheStatus::Hit { .. })
+    }
+}
+
+/// Statistics of the cache of a memoized function `f` since the start of the program, as returned
+/// by `memoized_stats_f()`.
+///
+/// The counters are shared by all threads, also for thread-local caches, and by all instances for
+/// `PerInstance` caches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    /// Calls returning a cached value.
+    pub hits: u64,
+    /// Calls computing the value, including those finding an expired entry.
+    pub misses: u64,
+    /// Values stored in the cache, by calls or `memoized_prime_f()`.
+    pub inserts: u64,
+    /// Expired entries found by calls.
+    pub expirations: u64,
+    /// Entries evicted from a full `Capacity`-limited cache.
+    pub evictions: u64,
+    /// Flushes of the cache.
+    pub flushes: u64,
+}
+
+impl CacheStats {
+    /// Fraction of calls returning a cached value, or `None` if there were no calls.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let calls = self.hits + self.misses;
+        if calls == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / calls as f64)
+        }
+    }
+}
+
+/// The counters behind [`CacheStats`]. Updating them doesn't require a lock.
+///
+/// As there is one instance per memoized function, it also keeps track of whether the function is
+/// registered.
+pub struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+    expirations: AtomicU64,
+    evictions: AtomicU64,
+    flushes: AtomicU64,
+    registered: Once,
+}
+
+impl Default for Counters {
+    fn default() -> Counters {
+        Counters::new()
+    }
+}
+
+impl Counters {
+    pub const fn new() -> Counters {
+        Counters {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            inserts: AtomicU64::new(0),
+            expirations: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+            registered: Once::new(),
+        }
+    }
+
+    /// Registers the function with the cache `handle`, unless it is already registered.
+    pub fn register(&self, handle: CacheHandle) {
+        self.registered
+            .call_once(|| crate::registry::register(handle));
+    }
+
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn insert(&self) {
+        self.inserts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn expiration(&self) {
+        self.expirations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn flush(&self) {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            inserts: self.inserts.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+        }
+    }
+}